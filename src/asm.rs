@@ -0,0 +1,442 @@
+//! Assembly comparison interfaces
+
+use std::collections::HashMap;
+
+use futures::Future as StdFuture;
+use hyper::client::connect::Connect;
+
+use compile::{self, Target};
+use diff;
+use {AsmFlavor, Client, CompileRequest, Future};
+
+/// Normalizes assembly so that unrelated builds compare equal
+///
+/// Comments and blank lines are dropped, trailing whitespace is trimmed,
+/// local labels such as `.LBB0_1` are renumbered as `.L0`, `.L1`, ... in
+/// order of first appearance and symbol hashes such as `::h5b1c...` are
+/// removed.
+pub fn normalize(asm: &str, flavor: &AsmFlavor) -> String {
+    let mut labels = HashMap::new();
+    let mut out = String::new();
+    for line in asm.lines() {
+        let line = strip_hashes(&rename_labels(
+            strip_comment(line, flavor).trim_end(),
+            &mut labels,
+        ));
+        if !line.trim().is_empty() {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Removes a `#` comment, or a `;` one in Intel syntax where `;` doesn't
+/// separate statements, ignoring those within string literals
+fn strip_comment<'a>(line: &'a str, flavor: &AsmFlavor) -> &'a str {
+    let intel = *flavor == AsmFlavor::Intel;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..idx],
+            ';' if !quoted && intel => return &line[..idx],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+/// Renames `.L` prefixed local labels after their order of appearance
+fn rename_labels(line: &str, labels: &mut HashMap<String, usize>) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(idx) = rest.find(".L") {
+        let boundary = rest[..idx]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !is_symbol_char(c));
+        let len = rest[idx + 2..]
+            .find(|c: char| !is_symbol_char(c))
+            .unwrap_or(rest.len() - idx - 2);
+        out.push_str(&rest[..idx]);
+        if boundary && len > 0 {
+            let next = labels.len();
+            let label = &rest[idx..idx + 2 + len];
+            let id = *labels.entry(label.to_owned()).or_insert(next);
+            out.push_str(&format!(".L{}", id));
+            rest = &rest[idx + 2 + len..];
+        } else {
+            out.push_str(".L");
+            rest = &rest[idx + 2..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Removes the `::h` prefixed hashes rustc appends to demangled symbols
+fn strip_hashes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(idx) = rest.find("::h") {
+        let hash = &rest[idx + 3..];
+        let len = hash.find(|c: char| !c.is_digit(16)).unwrap_or(hash.len());
+        if len == 16 {
+            out.push_str(&rest[..idx]);
+            rest = &hash[len..];
+        } else {
+            out.push_str(&rest[..idx + 3]);
+            rest = hash;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A source location given by a `.loc` directive
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// The source file, if declared by a `.file` directive
+    pub file: Option<String>,
+    /// The 1-based source line
+    pub line: u32,
+    /// The source column, 0 if unknown
+    pub column: u32,
+    /// The index of the symbol's first instruction at this location
+    pub instruction: usize,
+}
+
+/// The assembly of a single symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// The demangled symbol name
+    pub name: String,
+    /// The symbol's label and the lines following it, up to the next symbol
+    pub code: String,
+    /// Number of instructions, excluding labels and directives
+    pub instructions: usize,
+    /// Source locations, in order, when assembler directives are shown
+    pub locations: Vec<Location>,
+}
+
+impl Symbol {
+    /// Indicates if the symbol is named `name`, either fully or by its last
+    /// path segments, such as `sum` or `example::sum` for `example::sum`
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.name.ends_with(&format!("::{}", name))
+    }
+}
+
+/// Splits assembly output into its symbols
+///
+/// Lines preceding the first symbol, such as section directives, are
+/// skipped. Mangled names are demangled.
+pub fn symbols(asm: &str, flavor: &AsmFlavor) -> Vec<Symbol> {
+    let mut files = HashMap::new();
+    let mut symbols: Vec<Symbol> = Vec::new();
+    for line in asm.lines() {
+        let trimmed = strip_comment(line, flavor).trim();
+        let mut words = trimmed.split_whitespace();
+        match words.next() {
+            Some(".file") => {
+                if let Some((id, file)) = file_directive(words) {
+                    files.insert(id, file);
+                }
+            }
+            _ if is_symbol_label(line, flavor) => symbols.push(Symbol {
+                name: demangle(&trimmed[..trimmed.len() - 1]),
+                code: String::new(),
+                instructions: 0,
+                locations: Vec::new(),
+            }),
+            _ => (),
+        }
+        let symbol = match symbols.last_mut() {
+            Some(symbol) => symbol,
+            _ => continue,
+        };
+        symbol.code.push_str(line);
+        symbol.code.push('\n');
+        let mut words = trimmed.split_whitespace();
+        match words.next() {
+            Some(".loc") => {
+                let mut numbers = words.map(|word| word.parse::<u32>().ok());
+                if let (Some(Some(file)), Some(Some(line))) =
+                    (numbers.next(), numbers.next())
+                {
+                    symbol.locations.push(Location {
+                        file: files.get(&file).cloned(),
+                        line,
+                        column: numbers.next().and_then(|n| n).unwrap_or(0),
+                        instruction: symbol.instructions,
+                    })
+                }
+            }
+            Some(word) if !word.starts_with('.') && !word.ends_with(':') => {
+                symbol.instructions += 1
+            }
+            _ => (),
+        }
+    }
+    symbols
+}
+
+/// Indicates if a line is a label for a non-local symbol
+fn is_symbol_label(line: &str, flavor: &AsmFlavor) -> bool {
+    let trimmed = strip_comment(line, flavor).trim_end();
+    !line.starts_with(char::is_whitespace)
+        && !trimmed.starts_with('.')
+        && trimmed.len() > 1
+        && trimmed.ends_with(':')
+}
+
+/// Parses the id and path of a `.file` directive's arguments
+fn file_directive<'a, I>(mut words: I) -> Option<(u32, String)>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let id = words.next()?.parse().ok()?;
+    // DWARF 5 directives declare a directory ahead of the file name
+    let parts = words
+        .take_while(|word| word.starts_with('"'))
+        .map(|word| word.trim_matches('"'))
+        .collect::<Vec<_>>();
+    match parts.len() {
+        1 => Some((id, parts[0].to_owned())),
+        2 if parts[1].starts_with('/') => Some((id, parts[1].to_owned())),
+        2 => Some((id, format!("{}/{}", parts[0], parts[1]))),
+        _ => None,
+    }
+}
+
+/// Demangles a legacy `_ZN...E` rust symbol, leaving other names as is
+///
+/// The trailing hash segment is dropped, as in rustc's demangled output.
+pub fn demangle(name: &str) -> String {
+    let mangled = if name.starts_with("_ZN") && name.ends_with('E') {
+        &name[3..name.len() - 1]
+    } else {
+        return name.to_owned();
+    };
+    let mut segments = Vec::new();
+    let mut rest = mangled;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_digit(10)).unwrap_or(rest.len());
+        let len = match rest[..digits].parse::<usize>() {
+            Ok(len) if digits + len <= rest.len() => len,
+            _ => return name.to_owned(),
+        };
+        segments.push(&rest[digits..digits + len]);
+        rest = &rest[digits + len..];
+    }
+    if segments.last().map_or(false, |last| {
+        last.len() == 17
+            && last.starts_with('h')
+            && last[1..].chars().all(|c| c.is_digit(16))
+    }) {
+        segments.pop();
+    }
+    segments
+        .iter()
+        .map(|segment| unescape(segment))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Replaces the `$..$` escapes of a legacy mangled path segment
+fn unescape(segment: &str) -> String {
+    let segment = if segment.starts_with("_$") {
+        &segment[1..]
+    } else {
+        segment
+    };
+    let mut out = String::with_capacity(segment.len());
+    let mut rest = segment;
+    while !rest.is_empty() {
+        if rest.starts_with("..") {
+            out.push_str("::");
+            rest = &rest[2..];
+        } else if rest.starts_with('$') {
+            let end = match rest[1..].find('$') {
+                Some(end) => end + 1,
+                _ => {
+                    out.push_str(rest);
+                    break;
+                }
+            };
+            let escaped = match &rest[1..end] {
+                "SP" => Some('@'),
+                "BP" => Some('*'),
+                "RF" => Some('&'),
+                "LT" => Some('<'),
+                "GT" => Some('>'),
+                "LP" => Some('('),
+                "RP" => Some(')'),
+                "C" => Some(','),
+                code if code.starts_with('u') => {
+                    u32::from_str_radix(&code[1..], 16)
+                        .ok()
+                        .and_then(::std::char::from_u32)
+                }
+                _ => None,
+            };
+            match escaped {
+                Some(c) => out.push(c),
+                None => out.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// The assembly of two compilations and their differences
+#[derive(Debug)]
+pub struct Diff {
+    /// The left hand compilation
+    pub left: compile::Response,
+    /// The right hand compilation
+    pub right: compile::Response,
+    /// A unified diff of the normalized assembly of both compilations,
+    /// empty if they don't differ
+    pub unified: String,
+}
+
+/// Compiles two requests to assembly and diffs their normalized output with
+/// `context` lines of context
+///
+/// The requests' targets are replaced with `Target::Asm`, so they may differ
+/// in source, mode, channel or assembly flavor.
+pub fn diff<C>(
+    client: &Client<C>,
+    left: CompileRequest,
+    right: CompileRequest,
+    context: usize,
+) -> Future<Diff>
+where
+    C: Clone + Connect + 'static,
+{
+    let flavors = (left.assembly_flavor(), right.assembly_flavor());
+    Box::new(
+        client
+            .compile(left.with_target(Target::Asm))
+            .join(client.compile(right.with_target(Target::Asm)))
+            .map(move |(left, right)| {
+                let unified = diff::unified(
+                    &normalize(&left.code, &flavors.0),
+                    &normalize(&right.code, &flavors.1),
+                    "left",
+                    "right",
+                    context,
+                );
+                Diff {
+                    left,
+                    right,
+                    unified,
+                }
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_labels_and_comments() {
+        let asm = r#"example::sum:
+	xorl	%eax, %eax   # zero
+.LBB0_1:
+	jmp	.LBB0_1
+
+	.ascii	"a#b"
+	callq	core::panicking::panic::h0123456789abcdef
+	leaq	.Lanon.0f4a.1(%rip), %rdi
+"#;
+        assert_eq!(
+            normalize(asm, &AsmFlavor::Att),
+            "example::sum:\n\txorl\t%eax, %eax\n.L0:\n\tjmp\t.L0\n\
+             \t.ascii\t\"a#b\"\n\tcallq\tcore::panicking::panic\n\
+             \tleaq\t.L1(%rip), %rdi\n"
+        );
+    }
+
+    #[test]
+    fn semicolons_only_start_intel_comments() {
+        let asm = "\tpushq\t%rbp; movq\t%rsp, %rbp\n";
+        assert_eq!(normalize(asm, &AsmFlavor::Att), asm);
+        assert_eq!(
+            normalize("\tpush\trbp ; save\n", &AsmFlavor::Intel),
+            "\tpush\trbp\n"
+        );
+    }
+
+    #[test]
+    fn splits_symbols() {
+        let asm = r#"	.text
+	.file	1 "/playground" "src/main.rs"
+	.section	.text._ZN10playground3sum17h0123456789abcdefE,"ax",@progbits
+_ZN10playground3sum17h0123456789abcdefE:
+.Lfunc_begin0:
+	.loc	1 2 0
+	xorl	%eax, %eax
+	.loc	1 3 5 prologue_end
+	addl	%esi, %edi
+.LBB0_1:
+	retq
+playground::main:
+	retq
+"#;
+        let symbols = symbols(asm, &AsmFlavor::Att);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "playground::sum");
+        assert!(symbols[0].matches("sum"));
+        assert!(!symbols[0].matches("um"));
+        assert_eq!(symbols[0].instructions, 3);
+        assert_eq!(
+            symbols[0].locations[1],
+            Location {
+                file: Some("/playground/src/main.rs".into()),
+                line: 3,
+                column: 5,
+                instruction: 1,
+            }
+        );
+        assert!(symbols[0].code.starts_with("_ZN10playground3sum"));
+        assert!(symbols[0].code.ends_with("\tretq\n"));
+        assert_eq!(symbols[1].name, "playground::main");
+        assert_eq!(symbols[1].instructions, 1);
+    }
+
+    #[test]
+    fn demangles_legacy_symbols() {
+        assert_eq!(
+            demangle(
+                "_ZN49_$LT$example..Foo$u20$as$u20$core..fmt..Debug$GT$3fmt\
+                 17h0123456789abcdefE"
+            ),
+            "<example::Foo as core::fmt::Debug>::fmt"
+        );
+        assert_eq!(demangle("main"), "main");
+    }
+
+    #[test]
+    fn label_numbering_ignores_original_names() {
+        assert_eq!(
+            normalize(".LBB3_7:\n\tjne .LBB3_7", &AsmFlavor::Att),
+            normalize(".LBB0_1:\n\tjne .LBB0_1", &AsmFlavor::Att)
+        );
+    }
+}
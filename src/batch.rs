@@ -0,0 +1,215 @@
+//! Batch evaluation interfaces
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, Read};
+use std::path::Path;
+
+use serde_json;
+
+use {execute, Error, ExecuteRequest};
+
+/// A named request to execute as part of a batch
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Case {
+    /// Identifies the case in reports
+    #[serde(default)]
+    pub name: String,
+    /// The request to execute
+    #[serde(flatten)]
+    pub request: ExecuteRequest,
+}
+
+impl Case {
+    /// Returns a new `Case` for the given request
+    pub fn new<N>(name: N, request: ExecuteRequest) -> Self
+    where
+        N: Into<String>,
+    {
+        Case {
+            name: name.into(),
+            request,
+        }
+    }
+}
+
+/// Reads one case per `.rs` file in a directory, ordered by file name
+pub fn read_dir<P>(dir: P) -> Result<Vec<Case>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.is_file() && path.extension().map_or(false, |ext| ext == "rs")
+    });
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let mut code = String::new();
+            File::open(&path)?.read_to_string(&mut code)?;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok(Case::new(
+                name,
+                ExecuteRequest::builder(code).build().unwrap(),
+            ))
+        })
+        .collect()
+}
+
+/// Reads one case per line of newline delimited json
+///
+/// Each line holds an `ExecuteRequest` with an optional `name` field. Unnamed
+/// cases are named after their line number. Blank lines are skipped.
+pub fn read_ndjson<R>(reader: R) -> Result<Vec<Case>, Error>
+where
+    R: BufRead,
+{
+    let mut cases = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut case = serde_json::from_str::<Case>(&line)?;
+        if case.name.is_empty() {
+            case.name = format!("line {}", idx + 1);
+        }
+        cases.push(case);
+    }
+    Ok(cases)
+}
+
+/// The result of executing a case
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// The case name
+    pub name: String,
+    /// Indicates if the case ran successfully
+    pub success: bool,
+    /// Stdout line output
+    pub stdout: String,
+    /// Stderr line output
+    pub stderr: String,
+    /// Describes why the request itself failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Report {
+    /// Returns a new `Report` for the result of executing a case
+    pub fn new<N>(name: N, result: Result<execute::Response, Error>) -> Self
+    where
+        N: Into<String>,
+    {
+        match result {
+            Ok(response) => Report {
+                name: name.into(),
+                success: response.success,
+                stdout: response.stdout,
+                stderr: response.stderr,
+                error: None,
+            },
+            Err(err) => Report {
+                name: name.into(),
+                success: false,
+                stdout: String::new(),
+                stderr: String::new(),
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
+
+/// Pass and fail totals for a batch
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Number of successful cases
+    pub passed: usize,
+    /// Number of failed cases
+    pub failed: usize,
+}
+
+impl Summary {
+    /// Tallies a list of reports
+    pub fn new(reports: &[Report]) -> Self {
+        let passed = reports.iter().filter(|report| report.success).count();
+        Summary {
+            passed,
+            failed: reports.len() - passed,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "batch result: {}. {} passed; {} failed",
+            if self.failed == 0 { "ok" } else { "FAILED" },
+            self.passed,
+            self.failed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Channel;
+
+    #[test]
+    fn read_ndjson_names_unnamed_cases() {
+        let input = r#"{"name": "hello", "code": "fn main() {}"}
+
+{"code": "fn main() {}", "channel": "nightly"}"#;
+        let cases = read_ndjson(input.as_bytes()).unwrap();
+        assert_eq!(
+            cases,
+            vec![
+                Case::new(
+                    "hello",
+                    ExecuteRequest::builder("fn main() {}").build().unwrap()
+                ),
+                Case::new(
+                    "line 3",
+                    ExecuteRequest::builder("fn main() {}")
+                        .channel(Channel::Nightly)
+                        .build()
+                        .unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_counts_failures() {
+        let reports = vec![
+            Report::new("a", Err(Error::Timeout)),
+            Report {
+                name: "b".into(),
+                success: true,
+                stdout: String::new(),
+                stderr: String::new(),
+                error: None,
+            },
+        ];
+        let summary = Summary::new(&reports);
+        assert_eq!(
+            summary,
+            Summary {
+                passed: 1,
+                failed: 1,
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
+            "batch result: FAILED. 1 passed; 1 failed"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Request file interfaces
+//!
+//! A request file holds a request for one endpoint, named by its `endpoint`
+//! field, with its code inline or in a file referenced by `path`, relative
+//! to the request file. Unknown fields are errors.
+//!
+//! ```toml
+//! endpoint = "execute"
+//! channel = "nightly"
+//! mode = "release"
+//! path = "main.rs"
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde_json::{self, Value};
+use serde_yaml;
+use toml;
+
+use {compile, execute, format, lint};
+use {CompileRequest, Error, ExecuteRequest, FormatRequest, LintRequest};
+
+/// Request file formats
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileFormat {
+    /// `.toml` files
+    Toml,
+    /// `.json` files
+    Json,
+    /// `.yaml` and `.yml` files
+    Yaml,
+}
+
+impl FileFormat {
+    /// Returns the format of a file by its extension
+    pub fn of(path: &Path) -> Option<FileFormat> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Some(FileFormat::Toml),
+            Some("json") => Some(FileFormat::Json),
+            Some("yaml") | Some("yml") => Some(FileFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// A request for one of the playground's endpoints
+#[derive(Debug, PartialEq)]
+pub enum Case {
+    /// A request for `Client::execute`
+    Execute(ExecuteRequest),
+    /// A request for `Client::compile`
+    Compile(CompileRequest),
+    /// A request for `Client::format`
+    Format(FormatRequest),
+    /// A request for `Client::lint`, named `lint` or `clippy`
+    Lint(LintRequest),
+}
+
+impl Case {
+    /// Reads a request file, whose format is given by its extension
+    pub fn open<P>(path: P) -> Result<Case, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let format = FileFormat::of(path).ok_or_else(|| {
+            Error::Case(format!(
+                "{} is not a .toml, .json or .yaml file",
+                path.display()
+            ))
+        })?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Case::parse(&read(path)?, &format, dir)
+    }
+
+    /// Parses the contents of a request file, whose `path` is relative to
+    /// `dir`
+    pub fn parse(
+        contents: &str,
+        format: &FileFormat,
+        dir: &Path,
+    ) -> Result<Case, Error> {
+        let value = match *format {
+            FileFormat::Toml => toml::from_str::<Value>(contents)?,
+            FileFormat::Json => serde_json::from_str::<Value>(contents)?,
+            FileFormat::Yaml => serde_yaml::from_str::<Value>(contents)?,
+        };
+        let mut fields = match value {
+            Value::Object(fields) => fields,
+            _ => return Err(Error::Case("expected a table of fields".into())),
+        };
+        // the endpoint is read first so the rest of the fields can be read
+        // strictly as its request
+        let endpoint = match fields.remove("endpoint") {
+            Some(Value::String(endpoint)) => endpoint,
+            _ => return Err(Error::Case("endpoint must be a string".into())),
+        };
+        match (fields.remove("path"), fields.contains_key("code")) {
+            (Some(_), true) => {
+                return Err(Error::Case("set code or path, not both".into()))
+            }
+            (Some(path), false) => {
+                let path = path.as_str().ok_or_else(|| {
+                    Error::Case("path must be a string".into())
+                })?;
+                let code = read(&dir.join(path))?;
+                fields.insert("code".into(), code.into());
+            }
+            (None, true) => (),
+            (None, false) => {
+                return Err(Error::Case("set code or path".into()))
+            }
+        }
+        let request = Value::Object(fields);
+        let case = match endpoint.as_str() {
+            "execute" => serde_json::from_value(request).map(Case::Execute),
+            "compile" => serde_json::from_value(request).map(Case::Compile),
+            "format" => serde_json::from_value(request).map(Case::Format),
+            "lint" | "clippy" => {
+                serde_json::from_value(request).map(Case::Lint)
+            }
+            _ => {
+                return Err(Error::Case(format!(
+                    "unknown endpoint {}",
+                    endpoint
+                )))
+            }
+        };
+        case.map_err(|err| Error::Case(err.to_string()))
+    }
+}
+
+/// The response to a request file's request
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Response {
+    /// An execute response
+    Execute(execute::Response),
+    /// A compile response
+    Compile(compile::Response),
+    /// A format response
+    Format(format::Response),
+    /// A lint response
+    Lint(lint::Response),
+}
+
+impl Response {
+    /// Indicates if request was successful or not
+    pub fn success(&self) -> bool {
+        match *self {
+            Response::Execute(ref response) => response.success,
+            Response::Compile(ref response) => response.success,
+            Response::Format(ref response) => response.success,
+            Response::Lint(ref response) => response.success,
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use {Channel, Mode};
+
+    #[test]
+    fn parses_every_format() {
+        let dir = Path::new("");
+        let expected = ExecuteRequest::builder("fn main() {}")
+            .channel(Channel::Nightly)
+            .build()
+            .unwrap();
+        let cases = [
+            (
+                FileFormat::Toml,
+                "endpoint = \"execute\"\nchannel = \"nightly\"\n\
+                 code = \"fn main() {}\"",
+            ),
+            (
+                FileFormat::Json,
+                r#"{"endpoint": "execute", "channel": "nightly",
+                    "code": "fn main() {}"}"#,
+            ),
+            (
+                FileFormat::Yaml,
+                "endpoint: execute\nchannel: nightly\ncode: fn main() {}",
+            ),
+        ];
+        for &(ref format, contents) in &cases {
+            assert_eq!(
+                Case::parse(contents, format, dir).unwrap(),
+                Case::Execute(expected.clone())
+            );
+        }
+        assert!(Case::parse("endpoint = \"deploy\"", &FileFormat::Toml, dir)
+            .is_err());
+    }
+
+    #[test]
+    fn reads_code_from_path() {
+        let dir =
+            env::temp_dir().join(format!("recess-case-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        let case = Case::parse(
+            "endpoint = \"compile\"\nmode = \"release\"\npath = \"main.rs\"",
+            &FileFormat::Toml,
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(
+            case,
+            Case::Compile(
+                CompileRequest::builder("fn main() {}")
+                    .mode(Mode::Release)
+                    .build()
+                    .unwrap()
+            )
+        );
+        assert!(Case::parse(
+            "endpoint = \"lint\"\ncode = \"\"\npath = \"main.rs\"",
+            &FileFormat::Toml,
+            &dir,
+        )
+        .is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let dir = Path::new("");
+        assert!(Case::parse(
+            "endpoint: execute\nchanel: nightly\ncode: fn main() {}",
+            &FileFormat::Yaml,
+            dir
+        )
+        .is_err());
+        assert!(Case::parse(
+            "endpoint: clippy\ncode: fn main() {}",
+            &FileFormat::Yaml,
+            dir
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn requires_code_or_path() {
+        assert!(Case::parse(
+            "endpoint = \"format\"",
+            &FileFormat::Toml,
+            Path::new("")
+        )
+        .is_err());
+    }
+}
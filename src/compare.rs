@@ -0,0 +1,259 @@
+//! Cross-channel comparison interfaces
+
+use futures::Future as StdFuture;
+use hyper::client::connect::Connect;
+
+use {
+    compile, execute, Channel, Client, CompileRequest, Error, ExecuteRequest,
+    Future, Mode,
+};
+
+/// The outcome of running a request on one channel and mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    /// The channel the request ran on
+    pub channel: Channel,
+    /// The mode the request ran in, if modes were compared
+    pub mode: Option<Mode>,
+    /// Indicates if the request succeeded
+    pub success: bool,
+    /// Stdout line output
+    pub stdout: String,
+    /// Stderr line output
+    pub stderr: String,
+    /// Describes why the request itself failed, if it did
+    pub error: Option<String>,
+}
+
+impl Variant {
+    fn new(
+        channel: Channel,
+        mode: Option<Mode>,
+        result: Result<(bool, String, String), Error>,
+    ) -> Self {
+        let (success, stdout, stderr, error) = match result {
+            Ok((success, stdout, stderr)) => (success, stdout, stderr, None),
+            Err(err) => {
+                (false, String::new(), String::new(), Some(err.to_string()))
+            }
+        };
+        Variant {
+            channel,
+            mode,
+            success,
+            stdout,
+            stderr,
+            error,
+        }
+    }
+
+    /// Names the variant, such as `nightly` or `nightly/release`
+    pub fn label(&self) -> String {
+        match self.mode {
+            Some(ref mode) => format!("{}/{}", self.channel, mode),
+            _ => self.channel.to_string(),
+        }
+    }
+}
+
+/// Indicates if any variants disagree on success, stdout or stderr
+///
+/// Cargo's status lines, such as `Finished` with its timing, are left out
+/// of the comparison of stderr.
+pub fn differs(variants: &[Variant]) -> bool {
+    variants.windows(2).any(|pair| {
+        pair[0].success != pair[1].success
+            || pair[0].stdout != pair[1].stdout
+            || without_status(&pair[0].stderr)
+                != without_status(&pair[1].stderr)
+            || pair[0].error != pair[1].error
+    })
+}
+
+/// Removes cargo's status lines, such as `Compiling playground v0.0.1` or
+/// `Running target/debug/playground`, from stderr
+fn without_status(stderr: &str) -> Vec<&str> {
+    const STATUSES: &[&str] = &[
+        "Compiling ",
+        "Checking ",
+        "Finished ",
+        "Running ",
+        "Blocking ",
+        "Updating ",
+        "Locking ",
+        "Downloading ",
+        "Downloaded ",
+    ];
+    stderr
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !STATUSES.iter().any(|status| line.starts_with(status))
+        })
+        .collect()
+}
+
+/// Lists every channel, paired with every mode if `modes` is true
+fn targets(modes: bool) -> Vec<(Channel, Option<Mode>)> {
+    let channels = vec![Channel::Stable, Channel::Beta, Channel::Nightly];
+    if !modes {
+        return channels
+            .into_iter()
+            .map(|channel| (channel, None))
+            .collect();
+    }
+    channels
+        .into_iter()
+        .flat_map(|channel| {
+            vec![
+                (channel.clone(), Some(Mode::Debug)),
+                (channel, Some(Mode::Release)),
+            ]
+        })
+        .collect()
+}
+
+/// Executes a request on every channel, and in both modes if `modes` is true
+///
+/// Variants are returned in channel order, stable first
+pub fn execute<C>(
+    client: &Client<C>,
+    request: &ExecuteRequest,
+    modes: bool,
+) -> Future<Vec<Variant>>
+where
+    C: Clone + Connect + 'static,
+{
+    let client = client.clone();
+    compare(
+        request,
+        modes,
+        ExecuteRequest::variant,
+        move |requests, concurrency| client.execute_all(requests, concurrency),
+        |response: execute::Response| {
+            (response.success, response.stdout, response.stderr)
+        },
+    )
+}
+
+/// Compiles a request on every channel, and in both modes if `modes` is true
+///
+/// Variants are returned in channel order, stable first
+pub fn compile<C>(
+    client: &Client<C>,
+    request: &CompileRequest,
+    modes: bool,
+) -> Future<Vec<Variant>>
+where
+    C: Clone + Connect + 'static,
+{
+    let client = client.clone();
+    compare(
+        request,
+        modes,
+        CompileRequest::variant,
+        move |requests, concurrency| client.compile_all(requests, concurrency),
+        |response: compile::Response| {
+            (response.success, response.stdout, response.stderr)
+        },
+    )
+}
+
+/// Sends a variant of a request for every target, all at once, and pairs
+/// their success, stdout and stderr with their targets
+fn compare<R, T, V, A, P>(
+    request: &R,
+    modes: bool,
+    variant: V,
+    all: A,
+    parts: P,
+) -> Future<Vec<Variant>>
+where
+    T: Send + 'static,
+    V: Fn(&R, Channel, Option<Mode>) -> R,
+    A: FnOnce(Vec<R>, usize) -> Future<Vec<Result<T, Error>>>,
+    P: Fn(T) -> (bool, String, String) + Send + 'static,
+{
+    let targets = targets(modes);
+    let requests = targets
+        .iter()
+        .map(|&(ref channel, ref mode)| {
+            variant(request, channel.clone(), mode.clone())
+        })
+        .collect::<Vec<_>>();
+    let concurrency = targets.len();
+    Box::new(all(requests, concurrency).map(move |results| {
+        targets
+            .into_iter()
+            .zip(results)
+            .map(|((channel, mode), result)| {
+                Variant::new(channel, mode, result.map(&parts))
+            })
+            .collect()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_cover_channels_and_modes() {
+        assert_eq!(targets(false).len(), 3);
+        let targets = targets(true);
+        assert_eq!(targets.len(), 6);
+        assert_eq!(targets[1], (Channel::Stable, Some(Mode::Release)));
+    }
+
+    #[test]
+    fn differs_detects_disagreement() {
+        let stable = Variant::new(
+            Channel::Stable,
+            None,
+            Ok((true, "1\n".into(), String::new())),
+        );
+        let nightly = Variant {
+            channel: Channel::Nightly,
+            ..stable.clone()
+        };
+        assert_eq!(nightly.label(), "nightly");
+        assert!(!differs(&[stable.clone(), nightly.clone()]));
+        assert!(differs(&[
+            stable,
+            Variant {
+                stdout: "2\n".into(),
+                ..nightly
+            }
+        ]));
+    }
+
+    #[test]
+    fn differs_ignores_cargo_status_lines() {
+        let stderr = |secs| {
+            format!(
+                "   Compiling playground v0.0.1 (/playground)\n    \
+                 Finished dev [unoptimized] target(s) in {}s\n     \
+                 Running `target/debug/playground`\nwarning: boom\n",
+                secs
+            )
+        };
+        let stable = Variant::new(
+            Channel::Stable,
+            None,
+            Ok((true, String::new(), stderr("0.62"))),
+        );
+        let beta = Variant {
+            channel: Channel::Beta,
+            stderr: stderr("1.03"),
+            ..stable.clone()
+        };
+        assert!(!differs(&[stable.clone(), beta.clone()]));
+        assert!(differs(&[
+            stable,
+            Variant {
+                stderr: beta.stderr.replace("boom", "bang"),
+                ..beta
+            }
+        ]));
+    }
+}
@@ -3,14 +3,22 @@
 // https://github.com/colin-kiegel/rust-derive-builder/issues/104
 #![allow(unused_mut)]
 
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
-use {AsmFlavor, Channel, CrateType, Mode};
+use wasmprinter;
+use wat;
+
+use validation::{self, ValidationError};
+use {
+    AsmFlavor, Backtrace, Channel, CrateType, Defaults, Edition, Error, Mode,
+    OptLevel,
+};
 
 /// Compiler output targets
 ///
 /// The `Default` is `Asm`
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Target {
     #[serde(rename = "asm")]
     Asm,
@@ -49,7 +57,7 @@ impl FromStr for Target {
 }
 
 /// Demangling options
-#[derive(Debug, Serialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum DemangleAssembly {
     Demangle,
@@ -63,7 +71,7 @@ impl Default for DemangleAssembly {
 }
 
 /// Assembler visibility options
-#[derive(Debug, Serialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum HideAssemblerDirectives {
     Hide,
@@ -78,9 +86,13 @@ impl Default for HideAssemblerDirectives {
 
 // https://github.com/integer32llc/rust-playground/blob/4a49170ea46c4bae244a32b7e460534b56ccf02c/ui/src/main.rs#L523-L541
 /// Parameters for compiling rustlang code
-#[derive(Debug, Serialize, Default, Builder, PartialEq)]
+///
+/// `rustc_args`, `cfgs` and `env` are only sent when set. They're meant for
+/// backends which accept them: the public playground ignores them, so
+/// requests setting them fail validation when sent to it.
+#[derive(Debug, Serialize, Deserialize, Default, Builder, PartialEq, Clone)]
 #[builder(setter(into), default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
 pub struct Request {
     /// The desired compiler output format
     target: Target,
@@ -88,14 +100,30 @@ pub struct Request {
     assembly_flavor: Option<AsmFlavor>,
     demangle_assembly: DemangleAssembly,
     hide_assembler_directives: HideAssemblerDirectives,
-    channel: Channel,
-    mode: Mode,
-    #[serde(default)]
-    edition: String,
+    /// rust release channel, defaults to the client's channel
+    #[serde(serialize_with = "::or_default")]
+    channel: Option<Channel>,
+    /// compilation mode, defaults to the client's mode
+    #[serde(serialize_with = "::or_default")]
+    mode: Option<Mode>,
+    /// rust edition, defaults to the client's edition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edition: Option<Edition>,
     crate_type: CrateType,
     tests: bool,
-    #[serde(default)]
-    backtrace: bool,
+    backtrace: Backtrace,
+    /// optimization level, setting the mode where unset
+    #[serde(skip_serializing)]
+    opt_level: Option<OptLevel>,
+    /// extra rustc arguments, such as `-C target-cpu=native`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rustc_args: Vec<String>,
+    /// configuration flags, passed to rustc as `--cfg`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cfgs: Vec<String>,
+    /// environment variables for rustc
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
     /// code to compile
     code: String,
 }
@@ -118,11 +146,74 @@ impl Request {
     {
         RequestBuilder::default().code(code).clone()
     }
+
+    /// Returns a copy of this request for another channel and, optionally,
+    /// another mode
+    pub(crate) fn variant(&self, channel: Channel, mode: Option<Mode>) -> Self {
+        Request {
+            channel: Some(channel),
+            mode: mode.or_else(|| self.mode.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the assembly flavor, `Att` unless set
+    pub(crate) fn assembly_flavor(&self) -> AsmFlavor {
+        self.assembly_flavor.clone().unwrap_or_default()
+    }
+
+    /// Returns this request with another output target
+    pub(crate) fn with_target(self, target: Target) -> Self {
+        Request { target, ..self }
+    }
+
+    /// Checks for option combinations the playground can't serve
+    ///
+    /// An unset channel is treated as the playground's default, stable.
+    /// rustc arguments, cfgs and environment variables aren't checked here:
+    /// the `Client` only rejects them for the public playground, and other
+    /// hosts get them unchecked as there's no local compilation to check
+    /// them against.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.target == Target::Wasm && self.channel != Some(Channel::Nightly)
+        {
+            return Err(ValidationError::WasmRequiresNightly);
+        }
+        if self.assembly_flavor.is_some() && self.target != Target::Asm {
+            return Err(ValidationError::AsmFlavorWithoutAsm);
+        }
+        validation::opt_level(&self.opt_level, &self.mode)?;
+        validation::features(&self.channel, &self.code)?;
+        validation::tests(self.tests, &self.crate_type, &self.code)
+    }
+
+    /// Indicates if rustc arguments, cfgs or environment variables are set
+    pub(crate) fn rustc_options(&self) -> bool {
+        !self.rustc_args.is_empty()
+            || !self.cfgs.is_empty()
+            || !self.env.is_empty()
+    }
+
+    /// Fills in options left unset with the client's defaults
+    pub(crate) fn merge(&mut self, defaults: &Defaults) {
+        if self.mode.is_none() {
+            self.mode = self.opt_level.as_ref().and_then(OptLevel::mode);
+        }
+        if self.channel.is_none() {
+            self.channel = defaults.channel.clone();
+        }
+        if self.mode.is_none() {
+            self.mode = defaults.mode.clone();
+        }
+        if self.edition.is_none() {
+            self.edition = defaults.edition.clone();
+        }
+    }
 }
 
 // https://github.com/integer32llc/rust-playground/blob/4a49170ea46c4bae244a32b7e460534b56ccf02c/ui/src/main.rs#L543-L549
 /// Compile operation response
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
     /// Indicates if request was successful or not
     pub success: bool,
@@ -134,10 +225,111 @@ pub struct Response {
     pub stderr: String,
 }
 
+impl Response {
+    /// Decodes the compiled code of a `Target::Wasm` request
+    pub fn wasm(&self) -> Result<WasmModule, Error> {
+        WasmModule::parse(&self.code)
+    }
+}
+
+/// A WebAssembly module returned for `Target::Wasm` requests
+#[derive(Debug, Clone, PartialEq)]
+pub enum WasmModule {
+    /// A module in the WebAssembly text format
+    Text(String),
+    /// A module in the WebAssembly binary format
+    Binary(Vec<u8>),
+}
+
+impl WasmModule {
+    /// Parses compiled code, which the playground returns in the text
+    /// format
+    ///
+    /// A binary module can't be carried by the response's UTF-8 `code`
+    /// unchanged, so one is an error rather than corrupted bytes.
+    pub fn parse(code: &str) -> Result<Self, Error> {
+        if !code.trim_start().starts_with("(module") {
+            return Err(Error::Wasm(
+                "expected a (module ...) definition".into(),
+            ));
+        }
+        Ok(WasmModule::Text(code.to_owned()))
+    }
+
+    /// Returns the module in the binary format
+    pub fn to_binary(&self) -> Result<Vec<u8>, Error> {
+        match *self {
+            WasmModule::Text(ref text) => {
+                wat::parse_str(text).map_err(|err| Error::Wasm(err.to_string()))
+            }
+            WasmModule::Binary(ref bytes) => Ok(bytes.clone()),
+        }
+    }
+
+    /// Returns the module in the text format
+    pub fn to_text(&self) -> Result<String, Error> {
+        match *self {
+            WasmModule::Text(ref text) => Ok(text.clone()),
+            WasmModule::Binary(ref bytes) => wasmprinter::print_bytes(bytes)
+                .map_err(|err| Error::Wasm(err.to_string())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn validates_option_combinations() {
+        let mut request = Request::builder("foo").target(Target::Wasm).clone();
+        assert_eq!(
+            request.clone().build().unwrap().validate(),
+            Err(ValidationError::WasmRequiresNightly)
+        );
+        assert_eq!(
+            request
+                .clone()
+                .channel(Channel::Nightly)
+                .build()
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            request
+                .channel(Channel::Nightly)
+                .assembly_flavor(AsmFlavor::Intel)
+                .build()
+                .unwrap()
+                .validate(),
+            Err(ValidationError::AsmFlavorWithoutAsm)
+        );
+        assert_eq!(
+            Request::builder("fn main() {}")
+                .tests(true)
+                .build()
+                .unwrap()
+                .validate(),
+            Err(ValidationError::NoTests)
+        );
+    }
+
+    #[test]
+    fn wasm_modules_convert_between_formats() {
+        let module =
+            WasmModule::parse("(module (func (export \"f\")))").unwrap();
+        let binary = module.to_binary().unwrap();
+        assert!(binary.starts_with(b"\0asm"));
+        assert!(WasmModule::Binary(binary)
+            .to_text()
+            .unwrap()
+            .contains("(export \"f\""));
+        assert!(WasmModule::parse("\t.text").is_err());
+        let binary = String::from_utf8_lossy(b"\0asm\x01\0\0\0\x80\xff");
+        assert!(WasmModule::parse(&binary).is_err());
+    }
+
     #[test]
     fn compile_builder_defaults() {
         assert_eq!(
@@ -147,15 +339,44 @@ mod tests {
                 assembly_flavor: None,
                 demangle_assembly: DemangleAssembly::Demangle,
                 hide_assembler_directives: HideAssemblerDirectives::Hide,
-                channel: Channel::Stable,
-                mode: Mode::Debug,
-                edition: String::new(),
+                channel: None,
+                mode: None,
+                edition: None,
                 crate_type: CrateType::Binary,
                 tests: false,
-                backtrace: false,
+                backtrace: Backtrace::Auto,
+                opt_level: None,
+                rustc_args: Vec::new(),
+                cfgs: Vec::new(),
+                env: BTreeMap::new(),
                 code: String::from("foo"),
             }
         )
     }
 
+    #[test]
+    fn compile_serializes_unset_options_as_defaults() {
+        let json =
+            ::serde_json::to_value(Request::builder("foo").build().unwrap())
+                .unwrap();
+        assert_eq!(json["channel"], "stable");
+        assert_eq!(json["mode"], "debug");
+        assert!(json.get("edition").is_none());
+    }
+
+    #[test]
+    fn compile_merge_prefers_request_options() {
+        let mut request = Request::builder("foo")
+            .channel(Channel::Beta)
+            .build()
+            .unwrap();
+        request.merge(&Defaults {
+            channel: Some(Channel::Nightly),
+            mode: Some(Mode::Release),
+            edition: Some(Edition::Edition2018),
+        });
+        assert_eq!(request.channel, Some(Channel::Beta));
+        assert_eq!(request.mode, Some(Mode::Release));
+        assert_eq!(request.edition, Some(Edition::Edition2018));
+    }
 }
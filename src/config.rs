@@ -0,0 +1,324 @@
+//! Configuration file interfaces
+//!
+//! Settings are read from `recess.toml` files: the user's, in
+//! `$XDG_CONFIG_HOME/recess`, then the nearest one in the working directory
+//! or its parents, whose settings take precedence. Top level settings form
+//! the default profile, which `[profiles.<name>]` tables override.
+//!
+//! ```toml
+//! channel = "nightly"
+//! crate_type = "lib"
+//!
+//! [profiles.internal]
+//! host = "https://playground.example.com"
+//! timeout = 30
+//! format = "json"
+//! ```
+//!
+//! `RECESS_HOST`, `RECESS_CHANNEL`, `RECESS_MODE`, `RECESS_EDITION`,
+//! `RECESS_CRATE_TYPE`, `RECESS_TIMEOUT` and `RECESS_FORMAT` environment
+//! variables override the selected profile.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+
+use toml;
+use toml::value::{Table, Value};
+
+#[cfg(feature = "tls")]
+use Client;
+use {Channel, CrateType, Edition, Error, Mode};
+
+/// The name of configuration files
+pub const FILE_NAME: &str = "recess.toml";
+
+/// How the CLI prints responses
+///
+/// The `Default` is `Text`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Response output as is
+    Text,
+    /// Responses as JSON documents
+    Json,
+}
+
+impl OutputFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["text", "json"]
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Text
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err("invalid output format"),
+        }
+    }
+}
+
+/// A named set of settings
+///
+/// Unknown settings, such as a misspelled `chanel`, are errors.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// playground host, such as a self-hosted playground's
+    pub host: Option<String>,
+    /// rust release channel for requests which don't set one
+    pub channel: Option<Channel>,
+    /// compilation mode for requests which don't set one
+    pub mode: Option<Mode>,
+    /// rust edition for requests which don't set one
+    pub edition: Option<Edition>,
+    /// crate type for the CLI's execute and compile requests, unless their
+    /// source or flags set one
+    pub crate_type: Option<CrateType>,
+    /// request timeout, in seconds, which must be at least 1
+    pub timeout: Option<u64>,
+    /// how the CLI prints responses
+    pub format: Option<OutputFormat>,
+}
+
+impl Profile {
+    /// Reads the settings of `RECESS_*` environment variables
+    pub fn from_env() -> Result<Profile, Error> {
+        Profile::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars<F>(var: F) -> Result<Profile, Error>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        fn parse<T, F>(var: &F, name: &str) -> Result<Option<T>, Error>
+        where
+            T: FromStr,
+            F: Fn(&str) -> Option<String>,
+        {
+            match var(name).filter(|value| !value.trim().is_empty()) {
+                Some(value) => value.trim().parse().map(Some).map_err(|_| {
+                    Error::Config(format!("invalid {} {}", name, value))
+                }),
+                _ => Ok(None),
+            }
+        }
+        Ok(Profile {
+            host: parse(&var, "RECESS_HOST")?,
+            channel: parse(&var, "RECESS_CHANNEL")?,
+            mode: parse(&var, "RECESS_MODE")?,
+            edition: parse(&var, "RECESS_EDITION")?,
+            crate_type: parse(&var, "RECESS_CRATE_TYPE")?,
+            timeout: parse(&var, "RECESS_TIMEOUT")?,
+            format: parse(&var, "RECESS_FORMAT")?,
+        })
+    }
+
+    /// Overrides settings with those set in `other`
+    pub fn merge(&mut self, other: &Profile) {
+        fn set<T: Clone>(setting: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                *setting = other.clone();
+            }
+        }
+        set(&mut self.host, &other.host);
+        set(&mut self.channel, &other.channel);
+        set(&mut self.mode, &other.mode);
+        set(&mut self.edition, &other.edition);
+        set(&mut self.crate_type, &other.crate_type);
+        set(&mut self.timeout, &other.timeout);
+        set(&mut self.format, &other.format);
+    }
+
+    /// Creates a `Client` with this profile's host, timeout and request
+    /// defaults
+    #[cfg(feature = "tls")]
+    pub fn client(&self) -> Result<Client, Error> {
+        let mut builder = Client::builder();
+        if let Some(ref host) = self.host {
+            builder.host(host.as_str());
+        }
+        if let Some(ref channel) = self.channel {
+            builder.channel(channel.clone());
+        }
+        if let Some(ref mode) = self.mode {
+            builder.mode(mode.clone());
+        }
+        if let Some(ref edition) = self.edition {
+            builder.edition(edition.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            builder.timeout(Duration::from_secs(timeout));
+        }
+        builder.build()
+    }
+}
+
+/// The contents of `recess.toml` files
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    /// Top level settings
+    pub default: Profile,
+    /// Named profiles, overriding the top level settings
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// Parses the contents of a `recess.toml` file
+    pub fn parse(contents: &str) -> Result<Config, Error> {
+        // the top level table holds the default profile's settings along
+        // with the `profiles` table, which `Profile` would reject
+        let mut table = toml::from_str::<Table>(contents)?;
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into()?,
+            _ => BTreeMap::new(),
+        };
+        Ok(Config {
+            default: Value::Table(table).try_into()?,
+            profiles,
+        })
+    }
+
+    /// Reads the user's configuration file, then the nearest project one
+    /// from the working directory up, neither of which needs to exist
+    pub fn load() -> Result<Config, Error> {
+        let mut config = Config::default();
+        let dir = env::current_dir()?;
+        for path in user_file().into_iter().chain(project_file(&dir)) {
+            let mut contents = String::new();
+            File::open(&path)?.read_to_string(&mut contents)?;
+            config.merge(Config::parse(&contents).map_err(|err| {
+                Error::Config(format!("{}: {}", path.display(), err))
+            })?);
+        }
+        Ok(config)
+    }
+
+    /// Overrides settings and profiles with those of `other`
+    pub fn merge(&mut self, other: Config) {
+        self.default.merge(&other.default);
+        for (name, profile) in other.profiles {
+            self.profiles
+                .entry(name)
+                .or_insert_with(Profile::default)
+                .merge(&profile);
+        }
+    }
+
+    /// Returns the top level settings overridden by those of a named
+    /// profile, if any
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, Error> {
+        let mut profile = self.default.clone();
+        if let Some(name) = name {
+            match self.profiles.get(name) {
+                Some(named) => profile.merge(named),
+                _ => {
+                    return Err(Error::Config(format!(
+                        "no profile named {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(profile)
+    }
+}
+
+/// Returns the path of the user's configuration file, if it exists
+fn user_file() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(dir.join("recess").join(FILE_NAME)).filter(|path| path.is_file())
+}
+
+/// Returns the path of the nearest configuration file in a directory or
+/// its parents
+fn project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_override_top_level_settings() {
+        let config = Config::parse(
+            r#"
+channel = "nightly"
+mode = "release"
+crate_type = "lib"
+
+[profiles.internal]
+host = "https://playground.example.com"
+mode = "debug"
+timeout = 30
+format = "json"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.profile(None).unwrap().mode, Some(Mode::Release));
+        let profile = config.profile(Some("internal")).unwrap();
+        assert_eq!(profile.channel, Some(Channel::Nightly));
+        assert_eq!(profile.mode, Some(Mode::Debug));
+        assert_eq!(profile.crate_type, Some(CrateType::Library));
+        assert_eq!(profile.timeout, Some(30));
+        assert_eq!(profile.format, Some(OutputFormat::Json));
+        assert!(config.profile(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn unknown_settings_are_errors() {
+        assert!(Config::parse("chanel = \"nightly\"").is_err());
+        assert!(Config::parse("[profiles.ci]\nmod = \"release\"").is_err());
+    }
+
+    #[test]
+    fn later_files_take_precedence() {
+        let mut config =
+            Config::parse("channel = \"beta\"\nedition = \"2018\"").unwrap();
+        config.merge(Config::parse("channel = \"nightly\"").unwrap());
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.channel, Some(Channel::Nightly));
+        assert_eq!(profile.edition, Some(Edition::Edition2018));
+    }
+
+    #[test]
+    fn reads_env_overrides() {
+        let profile = Profile::from_vars(|name| match name {
+            "RECESS_CHANNEL" => Some("beta".into()),
+            "RECESS_TIMEOUT" => Some("5".into()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(profile.channel, Some(Channel::Beta));
+        assert_eq!(profile.timeout, Some(5));
+        assert!(Profile::from_vars(|name| if name == "RECESS_MODE" {
+            Some("fast".into())
+        } else {
+            None
+        })
+        .is_err());
+    }
+}
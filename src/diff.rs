@@ -0,0 +1,371 @@
+//! Text diffing interfaces
+
+/// Renders texts as side by side columns, one row per line
+///
+/// Each column is headed by its label and fit to an equal share of `width`.
+/// Rows where the columns disagree are marked with `!`.
+pub fn side_by_side(columns: &[(String, String)], width: usize) -> String {
+    if columns.is_empty() {
+        return String::new();
+    }
+    let separator = " | ";
+    let cell = (width
+        .saturating_sub(2 + separator.len() * (columns.len() - 1))
+        / columns.len())
+    .max(8);
+    let lines = columns
+        .iter()
+        .map(|&(_, ref text)| text.lines().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let rows = lines.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = String::new();
+    let header = columns
+        .iter()
+        .map(|&(ref label, _)| fit(label, cell))
+        .collect::<Vec<_>>();
+    push_row(&mut out, "  ", &header, separator);
+    push_row(
+        &mut out,
+        "  ",
+        &vec!["-".repeat(cell); columns.len()],
+        separator,
+    );
+    for row in 0..rows {
+        let cells = lines
+            .iter()
+            .map(|lines| lines.get(row).cloned())
+            .collect::<Vec<_>>();
+        let marker = if cells.windows(2).all(|pair| pair[0] == pair[1]) {
+            "  "
+        } else {
+            "! "
+        };
+        let cells = cells
+            .iter()
+            .map(|line| fit(line.unwrap_or(""), cell))
+            .collect::<Vec<_>>();
+        push_row(&mut out, marker, &cells, separator);
+    }
+    out
+}
+
+/// A line of an edit script
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+/// The largest LCS table, in cells, built to diff two texts at once
+///
+/// Larger inputs are split in halves with Hirschberg's algorithm, which only
+/// keeps two table rows at a time.
+const MAX_TABLE: usize = 1 << 22;
+
+/// Returns the shortest edit script turning `old` into `new`, found through
+/// their longest common subsequence of lines
+fn script<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let prefix = old.iter().zip(new).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let mut edits = old[..prefix]
+        .iter()
+        .map(|line| Edit::Keep(line))
+        .collect::<Vec<_>>();
+    split(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+        MAX_TABLE,
+        &mut edits,
+    );
+    edits.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| Edit::Keep(line)),
+    );
+    edits
+}
+
+/// Pushes the edits turning `a` into `b`, splitting them in halves until
+/// their LCS table has at most `max_table` cells
+fn split<'a>(
+    a: &[&'a str],
+    b: &[&'a str],
+    max_table: usize,
+    edits: &mut Vec<Edit<'a>>,
+) {
+    if a.len() <= 1 || (a.len() + 1) * (b.len() + 1) <= max_table {
+        return table(a, b, edits);
+    }
+    let mid = a.len() / 2;
+    let forward = lcs_row(a[..mid].iter(), b.iter());
+    let backward = lcs_row(a[mid..].iter().rev(), b.iter().rev());
+    // the split of `b` whose halves share the most lines with those of `a`
+    let cut = (0..=b.len())
+        .max_by_key(|&j| (forward[j] + backward[b.len() - j], usize::MAX - j))
+        .unwrap_or(0);
+    split(&a[..mid], &b[..cut], max_table, edits);
+    split(&a[mid..], &b[cut..], max_table, edits);
+}
+
+/// Returns the common subsequence lengths of `a` and each prefix of `b`
+fn lcs_row<'a, 'b, A, B>(a: A, b: B) -> Vec<u32>
+where
+    'a: 'b,
+    A: Iterator<Item = &'b &'a str>,
+    B: Iterator<Item = &'b &'a str> + Clone,
+{
+    let mut row = vec![0u32; b.clone().count() + 1];
+    let mut next = row.clone();
+    for x in a {
+        for (j, y) in b.clone().enumerate() {
+            next[j + 1] = if x == y {
+                row[j] + 1
+            } else {
+                row[j + 1].max(next[j])
+            };
+        }
+        ::std::mem::swap(&mut row, &mut next);
+    }
+    row
+}
+
+/// Pushes the edits turning `a` into `b`, found through their full LCS table
+fn table<'a>(a: &[&'a str], b: &[&'a str], edits: &mut Vec<Edit<'a>>) {
+    // lcs[i * width + j] is the common subsequence length of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Keep(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len()
+            && (j == b.len()
+                || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            edits.push(Edit::Remove(a[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(b[j]));
+            j += 1;
+        }
+    }
+}
+
+/// Renders the differences between two texts as a unified diff with
+/// `context` lines of context around each change
+///
+/// Returns an empty string if the texts have the same lines.
+pub fn unified(
+    old: &str,
+    new: &str,
+    old_label: &str,
+    new_label: &str,
+    context: usize,
+) -> String {
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let edits = script(&old_lines, &new_lines);
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|&(_, edit)| match *edit {
+            Edit::Keep(_) => false,
+            _ => true,
+        })
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return String::new();
+    }
+    // the number of old and new lines preceding each edit
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in &edits {
+        positions.push((old_pos, new_pos));
+        match *edit {
+            Edit::Keep(_) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Remove(_) => old_pos += 1,
+            Edit::Add(_) => new_pos += 1,
+        }
+    }
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let mut idx = 0;
+    while idx < changes.len() {
+        let start = changes[idx].saturating_sub(context);
+        let mut end = changes[idx] + 1;
+        idx += 1;
+        while idx < changes.len() && changes[idx] - end <= 2 * context {
+            end = changes[idx] + 1;
+            idx += 1;
+        }
+        let end = (end + context).min(edits.len());
+        let hunk = &edits[start..end];
+        let old_len = hunk
+            .iter()
+            .filter(|edit| match **edit {
+                Edit::Add(_) => false,
+                _ => true,
+            })
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|edit| match **edit {
+                Edit::Remove(_) => false,
+                _ => true,
+            })
+            .count();
+        let (old_start, new_start) = positions[start];
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_len == 0 {
+                old_start
+            } else {
+                old_start + 1
+            },
+            old_len,
+            if new_len == 0 {
+                new_start
+            } else {
+                new_start + 1
+            },
+            new_len
+        ));
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Keep(line) => (' ', line),
+                Edit::Remove(line) => ('-', line),
+                Edit::Add(line) => ('+', line),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn push_row(out: &mut String, marker: &str, cells: &[String], separator: &str) {
+    out.push_str(marker);
+    out.push_str(cells.join(separator).trim_end());
+    out.push('\n');
+}
+
+/// Pads or truncates a line to exactly `width` characters
+fn fit(line: &str, width: usize) -> String {
+    let len = line.chars().count();
+    if len > width {
+        let mut fitted = line.chars().take(width - 1).collect::<String>();
+        fitted.push('>');
+        fitted
+    } else {
+        format!("{}{}", line, " ".repeat(width - len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_differing_rows() {
+        let columns = vec![
+            ("stable".to_string(), "a\nb".to_string()),
+            ("nightly".to_string(), "a\nc\nd".to_string()),
+        ];
+        assert_eq!(
+            side_by_side(&columns, 21),
+            "  stable   | nightly\n  -------- | --------\n  a        | a\n\
+             ! b        | c\n!          | d\n"
+        );
+    }
+
+    #[test]
+    fn renders_unified_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni";
+        assert_eq!(
+            unified(old, new, "old", "new", 1),
+            "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n\
+             @@ -8,1 +8,2 @@\n h\n+i\n"
+        );
+        assert_eq!(unified(old, old, "old", "new", 3), "");
+    }
+
+    #[test]
+    fn merges_nearby_changes() {
+        assert_eq!(
+            unified("a\nb\nc", "x\nb\ny", "old", "new", 1),
+            "--- old\n+++ new\n@@ -1,3 +1,3 @@\n-a\n+x\n b\n-c\n+y\n"
+        );
+    }
+
+    #[test]
+    fn splits_large_inputs_in_linear_space() {
+        let old = "a b c d e f g h i j".split(' ').collect::<Vec<_>>();
+        let new = "a x c d y f g h z j k".split(' ').collect::<Vec<_>>();
+        let (mut whole, mut halves) = (Vec::new(), Vec::new());
+        table(&old, &new, &mut whole);
+        split(&old, &new, 4, &mut halves);
+        let kept = |edits: &[Edit]| {
+            edits
+                .iter()
+                .filter(|edit| match **edit {
+                    Edit::Keep(_) => true,
+                    _ => false,
+                })
+                .count()
+        };
+        assert_eq!(kept(&halves), kept(&whole));
+        let sides = |keep: fn(&Edit) -> bool| {
+            halves
+                .iter()
+                .filter(|edit| keep(edit))
+                .map(|edit| match *edit {
+                    Edit::Keep(line) | Edit::Remove(line) | Edit::Add(line) => {
+                        line
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sides(|edit| match *edit {
+                Edit::Add(_) => false,
+                _ => true,
+            }),
+            old
+        );
+        assert_eq!(
+            sides(|edit| match *edit {
+                Edit::Remove(_) => false,
+                _ => true,
+            }),
+            new
+        );
+    }
+
+    #[test]
+    fn truncates_long_lines() {
+        assert_eq!(fit("0123456789", 8), "0123456>");
+        assert_eq!(fit("01", 4), "01  ");
+    }
+}
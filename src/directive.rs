@@ -0,0 +1,214 @@
+//! Source directive interfaces
+//!
+//! Comments at the top of a file, before its first line of code, can carry
+//! request options:
+//!
+//! ```text
+//! // recess: channel=nightly mode=release edition=2021 crate-type=lib tests
+//! ```
+//!
+//! Keys are `channel`, `mode`, `edition`, `crate-type`, `opt-level`,
+//! `backtrace` and `tests`, a key without a value meaning `true`.
+
+use std::str::FromStr;
+
+use {compile, execute};
+use {Backtrace, Channel, CrateType, Edition, Error, Mode, OptLevel};
+
+/// Marks a comment as holding directives
+pub const PREFIX: &str = "recess:";
+
+/// Sets the options of a request builder which directives set
+///
+/// Execute and compile request builders share their setters, so both are
+/// configured the same way.
+macro_rules! configure {
+    ($directives:expr, $builder:expr) => {{
+        let directives = $directives;
+        if let Some(ref channel) = directives.channel {
+            $builder.channel(channel.clone());
+        }
+        if let Some(ref mode) = directives.mode {
+            $builder.mode(mode.clone());
+        }
+        if let Some(ref edition) = directives.edition {
+            $builder.edition(edition.clone());
+        }
+        if let Some(ref crate_type) = directives.crate_type {
+            $builder.crate_type(crate_type.clone());
+        }
+        if let Some(ref opt_level) = directives.opt_level {
+            $builder.opt_level(opt_level.clone());
+        }
+        if let Some(ref backtrace) = directives.backtrace {
+            $builder.backtrace(backtrace.clone());
+        }
+        if let Some(tests) = directives.tests {
+            $builder.tests(tests);
+        }
+    }};
+}
+
+/// Request options set by a file's directives
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Directives {
+    /// rust release channel
+    pub channel: Option<Channel>,
+    /// compilation mode
+    pub mode: Option<Mode>,
+    /// rust edition
+    pub edition: Option<Edition>,
+    /// crate type
+    pub crate_type: Option<CrateType>,
+    /// optimization level
+    pub opt_level: Option<OptLevel>,
+    /// backtraces for panics
+    pub backtrace: Option<Backtrace>,
+    /// run the code's tests
+    pub tests: Option<bool>,
+}
+
+impl Directives {
+    /// Parses the directives of source code, which may follow a shebang and
+    /// attributes
+    ///
+    /// Unknown keys and invalid values are errors.
+    pub fn parse(code: &str) -> Result<Directives, Error> {
+        let mut directives = Directives::default();
+        for (idx, line) in code.lines().enumerate() {
+            let line = line.trim();
+            // skip blank lines, a shebang and attributes such as
+            // `#![feature(test)]`
+            if line.is_empty()
+                || line.starts_with("#!")
+                || line.starts_with("#[")
+            {
+                continue;
+            }
+            if !line.starts_with("//") {
+                break;
+            }
+            let comment = line.trim_start_matches('/').trim_start();
+            if !comment.starts_with(PREFIX) {
+                continue;
+            }
+            for option in comment[PREFIX.len()..].split_whitespace() {
+                directives.set(option).map_err(|err| {
+                    Error::Directive(format!("line {}: {}", idx + 1, err))
+                })?;
+            }
+        }
+        Ok(directives)
+    }
+
+    /// Sets the option of a `key=value` pair, or of a `key` meaning `true`
+    fn set(&mut self, option: &str) -> Result<(), String> {
+        fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid {} {}", key, value))
+        }
+        let (key, value) = match option.find('=') {
+            Some(idx) => (&option[..idx], &option[idx + 1..]),
+            _ => (option, "true"),
+        };
+        match key {
+            "channel" => self.channel = Some(parse(key, value)?),
+            "mode" => self.mode = Some(parse(key, value)?),
+            "edition" => self.edition = Some(parse(key, value)?),
+            "crate-type" => self.crate_type = Some(parse(key, value)?),
+            "opt-level" => self.opt_level = Some(parse(key, value)?),
+            "backtrace" => self.backtrace = Some(parse(key, value)?),
+            "tests" => self.tests = Some(parse(key, value)?),
+            _ => return Err(format!("unknown key {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Overrides options with those set in `other`
+    pub fn merge(&mut self, other: &Directives) {
+        fn set<T: Clone>(option: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                *option = other.clone();
+            }
+        }
+        set(&mut self.channel, &other.channel);
+        set(&mut self.mode, &other.mode);
+        set(&mut self.edition, &other.edition);
+        set(&mut self.crate_type, &other.crate_type);
+        set(&mut self.opt_level, &other.opt_level);
+        set(&mut self.backtrace, &other.backtrace);
+        set(&mut self.tests, &other.tests);
+    }
+
+    /// Sets the options of an execute request
+    pub fn configure_execute(&self, builder: &mut execute::RequestBuilder) {
+        configure!(self, builder);
+    }
+
+    /// Sets the options of a compile request
+    pub fn configure_compile(&self, builder: &mut compile::RequestBuilder) {
+        configure!(self, builder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ExecuteRequest;
+
+    #[test]
+    fn parses_leading_comments() {
+        let code = "#!/usr/bin/env recess\n";
+        assert_eq!(Directives::parse(code).unwrap(), Directives::default());
+        let code = "#!/usr/bin/env recess\n#![feature(test)]\n\
+                    // recess: channel=nightly\nfn main() {}\n";
+        assert_eq!(
+            Directives::parse(code).unwrap().channel,
+            Some(Channel::Nightly)
+        );
+        let code = "// A snippet\n\
+                    // recess: channel=nightly mode=release edition=2021\n\
+                    //recess: crate-type=lib tests\n\n\
+                    fn main() {}\n\
+                    // recess: channel=beta\n";
+        assert_eq!(
+            Directives::parse(code).unwrap(),
+            Directives {
+                channel: Some(Channel::Nightly),
+                mode: Some(Mode::Release),
+                edition: Some(Edition::Edition2021),
+                crate_type: Some(CrateType::Library),
+                tests: Some(true),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reports_unknown_keys_and_invalid_values() {
+        assert!(Directives::parse("// recess: optimize").is_err());
+        assert!(Directives::parse("// recess: channel=weekly").is_err());
+        assert!(Directives::parse("// recess: tests=maybe").is_err());
+    }
+
+    #[test]
+    fn merged_options_take_precedence() {
+        let mut directives =
+            Directives::parse("// recess: channel=nightly tests").unwrap();
+        directives.merge(&Directives {
+            channel: Some(Channel::Beta),
+            ..Default::default()
+        });
+        let mut builder = ExecuteRequest::builder("fn main() {}");
+        directives.configure_execute(&mut builder);
+        assert_eq!(
+            builder.build().unwrap(),
+            ExecuteRequest::builder("fn main() {}")
+                .channel(Channel::Beta)
+                .tests(true)
+                .build()
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Rustdoc example interfaces
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use hyper::client::connect::Connect;
+use toml;
+
+use markdown;
+use snippet::{self, Snippet, Status};
+use {Channel, Client, Edition, Error, Future};
+
+/// Extracts the code examples in the `///` and `//!` doc comments of rust
+/// source
+///
+/// Snippet lines refer to lines of the rust source. As with rustdoc, code
+/// blocks without an info string are considered rust.
+pub fn extract(source: &str) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    let mut block: Vec<(usize, &str)> = Vec::new();
+    let mut block_kind = None;
+    for (idx, line) in source.lines().enumerate() {
+        match doc_comment(line) {
+            Some((kind, text)) if block_kind.map_or(true, |k| k == kind) => {
+                block_kind = Some(kind);
+                block.push((idx + 1, text));
+            }
+            doc => {
+                snippets.extend(examples(&block));
+                block.clear();
+                block_kind = None;
+                if let Some((kind, text)) = doc {
+                    block_kind = Some(kind);
+                    block.push((idx + 1, text));
+                }
+            }
+        }
+    }
+    snippets.extend(examples(&block));
+    snippets
+}
+
+/// Returns the kind and text of a `///` or `//!` doc comment line
+fn doc_comment(line: &str) -> Option<(&'static str, &str)> {
+    let trimmed = line.trim_start();
+    let (kind, text) =
+        if trimmed.starts_with("///") && !trimmed.starts_with("////") {
+            ("///", &trimmed[3..])
+        } else if trimmed.starts_with("//!") {
+            ("//!", &trimmed[3..])
+        } else {
+            return None;
+        };
+    Some((
+        kind,
+        if text.starts_with(' ') {
+            &text[1..]
+        } else {
+            text
+        },
+    ))
+}
+
+/// Extracts the code blocks in a run of doc comment lines
+fn examples(block: &[(usize, &str)]) -> Vec<Snippet> {
+    let text = block
+        .iter()
+        .map(|&(_, text)| text)
+        .collect::<Vec<_>>()
+        .join("\n");
+    markdown::fenced(&text, true)
+        .into_iter()
+        .map(|mut snippet| {
+            snippet.line = block[snippet.line - 1].0;
+            snippet
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    package: Package,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    edition: Option<Edition>,
+}
+
+/// A crate whose doc examples are checked
+#[derive(Debug, Default)]
+pub struct Crate {
+    /// The crate name as referred to in rust code
+    pub name: String,
+    /// The edition declared in the crate's manifest
+    pub edition: Option<Edition>,
+    /// The crate's `src/lib.rs` source, if it is a single file library
+    pub lib: Option<String>,
+}
+
+impl Crate {
+    /// Opens the crate at `root`, a directory holding a `Cargo.toml` file
+    pub fn open<P>(root: P) -> Result<Crate, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let manifest =
+            toml::from_str::<Manifest>(&read(&root.join("Cargo.toml"))?)?;
+        let lib_path = root.join("src").join("lib.rs");
+        let lib = if lib_path.is_file() {
+            Some(read(&lib_path)?).filter(|lib| !declares_modules(lib))
+        } else {
+            None
+        };
+        Ok(Crate {
+            name: manifest.package.name.replace('-', "_"),
+            edition: manifest.package.edition,
+            lib,
+        })
+    }
+
+    /// Returns a snippet's code as a complete program
+    ///
+    /// When the crate is a single file library, it is inlined as a module so
+    /// that examples may refer to its items by the crate's name.
+    pub fn program(&self, snippet: &Snippet) -> String {
+        let lib = match self.lib {
+            Some(ref lib) if !snippet.attributes.test_harness => lib,
+            _ => return snippet.program(),
+        };
+        let extern_crate = format!("extern crate {};", self.name);
+        let code = snippet::unhide(&snippet.code)
+            .lines()
+            .filter(|line| {
+                line.trim_start().trim_start_matches("#[macro_use]").trim()
+                    != extern_crate
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let program = snippet::wrap(&code);
+        // crate attributes must precede the inlined module
+        let attrs = program
+            .lines()
+            .take_while(|line| line.starts_with("#!["))
+            .chain(
+                lib.lines()
+                    .filter(|line| line.trim_start().starts_with("#![feature")),
+            )
+            .collect::<Vec<_>>();
+        let body = program
+            .lines()
+            .skip_while(|line| line.starts_with("#!["))
+            .collect::<Vec<_>>();
+        let module = lib
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("#!["))
+            .collect::<Vec<_>>();
+        format!(
+            "{}\n#[macro_use]\nmod {} {{\n{}\n}}\n{}\n",
+            attrs.join("\n"),
+            self.name,
+            module.join("\n"),
+            body.join("\n")
+        )
+    }
+}
+
+/// Indicates if library source declares modules in other files
+fn declares_modules(source: &str) -> bool {
+    source.lines().any(|line| {
+        let line = line.trim();
+        (line.starts_with("mod ") || line.starts_with("pub mod "))
+            && line.ends_with(';')
+    })
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Lists the `.rs` files under a directory, ordered by path
+pub fn sources<P>(dir: P) -> Result<Vec<PathBuf>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut sources = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            sources.extend(self::sources(&path)?);
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            sources.push(path);
+        }
+    }
+    sources.sort();
+    Ok(sources)
+}
+
+/// Runs a crate's doc example on a release channel
+pub fn run<C>(
+    client: &Client<C>,
+    krate: &Crate,
+    snippet: &Snippet,
+    channel: Channel,
+) -> Future<Status>
+where
+    C: Clone + Connect + 'static,
+{
+    let mut attributes = snippet.attributes.clone();
+    if attributes.edition.is_none() {
+        attributes.edition = krate.edition.clone();
+    }
+    snippet::check_program(
+        client,
+        krate.program(snippet),
+        &attributes,
+        Some(channel),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_doc_comment_examples() {
+        let source = r#"//! Crate docs
+//!
+//! ```
+//! assert!(true);
+//! ```
+
+/// Adds one
+///
+/// ```rust,should_panic
+/// # use std::process;
+/// panic!();
+/// ```
+////```
+//// not docs
+////```
+fn add_one() {}
+"#;
+        let snippets = extract(source);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].line, 3);
+        assert_eq!(snippets[0].code, "assert!(true);");
+        assert_eq!(snippets[1].line, 9);
+        assert_eq!(snippets[1].code, "# use std::process;\npanic!();");
+        assert!(snippets[1].attributes.should_panic);
+    }
+
+    #[test]
+    fn inlines_single_file_crates() {
+        let krate = Crate {
+            name: "adder".into(),
+            edition: None,
+            lib: Some(
+                "#![deny(missing_docs)]\npub fn one() -> u8 { 1 }".into(),
+            ),
+        };
+        let snippet = &extract(
+            "/// ```\n/// extern crate adder;\n/// # fn main() {\n/// \
+             assert_eq!(adder::one(), 1);\n/// # }\n/// ```",
+        )[0];
+        assert_eq!(
+            krate.program(snippet),
+            "#![allow(unused)]\n#[macro_use]\nmod adder {\n\
+             pub fn one() -> u8 { 1 }\n}\n\
+             fn main() {\nassert_eq!(adder::one(), 1);\n}\n"
+        );
+    }
+}
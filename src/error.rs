@@ -1,8 +1,16 @@
-//use hyper::error::UriError;
+use hyper::header::InvalidHeaderValue;
+use hyper::http::uri::InvalidUri;
 use hyper::Error as HttpError;
 use hyper::StatusCode;
+#[cfg(feature = "tls")]
+use native_tls::Error as TlsError;
 use serde_json::error::Error as SerdeError;
+use serde_yaml::Error as YamlError;
 use std::io::Error as IoError;
+use tokio_timer::{TimeoutError, TimerError};
+use toml::de::Error as TomlError;
+use execute::Outcome;
+use validation::ValidationError;
 
 #[derive(Fail, Debug)]
 pub enum Error {
@@ -13,8 +21,60 @@ pub enum Error {
     #[fail(display = "{}", _0)]
     Io(#[cause] IoError),
     #[fail(display = "{}", _0)]
-    Fault(StatusCode), //#[fail(display = "{}", _0)]
-                       //Uri(#[cause] UriError)
+    Fault(StatusCode),
+    #[fail(display = "{}", _0)]
+    Uri(#[cause] InvalidUri),
+    #[fail(display = "{}", _0)]
+    Header(#[cause] InvalidHeaderValue),
+    #[fail(display = "request timed out")]
+    Timeout,
+    #[cfg(feature = "tls")]
+    #[fail(display = "{}", _0)]
+    Tls(#[cause] TlsError),
+    #[fail(display = "{}", _0)]
+    Proxy(String),
+    #[fail(display = "{}", _0)]
+    Toml(#[cause] TomlError),
+    #[fail(display = "{}", _0)]
+    Yaml(#[cause] YamlError),
+    #[fail(display = "invalid request: {}", _0)]
+    Invalid(#[cause] ValidationError),
+    #[fail(display = "invalid wasm module: {}", _0)]
+    Wasm(String),
+    #[fail(display = "{}", _0)]
+    Project(String),
+    #[fail(display = "{}", _0)]
+    Program(Outcome),
+    #[fail(display = "invalid configuration: {}", _0)]
+    Config(String),
+    #[fail(display = "invalid request file: {}", _0)]
+    Case(String),
+    #[fail(display = "invalid directive: {}", _0)]
+    Directive(String),
+}
+
+impl From<SerdeError> for Error {
+    fn from(err: SerdeError) -> Self {
+        Error::Codec(err)
+    }
+}
+
+impl From<TomlError> for Error {
+    fn from(err: TomlError) -> Self {
+        Error::Toml(err)
+    }
+}
+
+impl From<YamlError> for Error {
+    fn from(err: YamlError) -> Self {
+        Error::Yaml(err)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(err: IoError) -> Self {
+        Error::Io(err)
+    }
 }
 
 impl From<HttpError> for Error {
@@ -23,6 +83,46 @@ impl From<HttpError> for Error {
     }
 }
 
+#[cfg(feature = "tls")]
+impl From<TlsError> for Error {
+    fn from(err: TlsError) -> Self {
+        Error::Tls(err)
+    }
+}
+
+impl From<InvalidUri> for Error {
+    fn from(err: InvalidUri) -> Self {
+        Error::Uri(err)
+    }
+}
+
+impl From<InvalidHeaderValue> for Error {
+    fn from(err: InvalidHeaderValue) -> Self {
+        Error::Header(err)
+    }
+}
+
+impl<F> From<TimeoutError<F>> for Error {
+    fn from(err: TimeoutError<F>) -> Self {
+        match err {
+            TimeoutError::TimedOut(_) => Error::Timeout,
+            other => Error::Io(other.into()),
+        }
+    }
+}
+
+impl From<TimerError> for Error {
+    fn from(err: TimerError) -> Self {
+        Error::Io(err.into())
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        Error::Invalid(err)
+    }
+}
+
 impl From<StatusCode> for Error {
     fn from(err: StatusCode) -> Self {
         Error::Fault(err)
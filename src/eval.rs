@@ -0,0 +1,341 @@
+//! Expression evaluation interfaces
+
+// https://github.com/colin-kiegel/rust-derive-builder/issues/104
+#![allow(unused_mut)]
+
+use std::str::FromStr;
+
+use execute;
+use {Backtrace, Channel, Defaults, Edition, ExecuteRequest, Mode};
+
+/// Lines of the wrapping program before the expression
+const PRELUDE: usize = 2;
+
+/// The endpoint evaluations are sent to
+///
+/// The `Default` is `Execute`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transport {
+    /// The `/execute` endpoint
+    Execute,
+    /// The playground's legacy `/evaluate.json` endpoint
+    Evaluate,
+}
+
+impl Transport {
+    pub fn variants() -> &'static [&'static str] {
+        &["execute", "evaluate"]
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::Execute
+    }
+}
+
+impl FromStr for Transport {
+    type Err = &'static str;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "execute" => Ok(Transport::Execute),
+            "evaluate" => Ok(Transport::Evaluate),
+            _ => Err("invalid transport"),
+        }
+    }
+}
+
+/// Parameters for evaluating an expression, or a block of statements ending
+/// in one
+#[derive(Debug, Default, Builder, PartialEq, Clone)]
+#[builder(setter(into), default)]
+pub struct Request {
+    /// rust release channel, defaults to the client's channel
+    channel: Option<Channel>,
+    /// compilation mode, defaults to the client's mode
+    mode: Option<Mode>,
+    /// rust edition, defaults to the client's edition
+    edition: Option<Edition>,
+    /// backtraces for panics, ignored by the `Evaluate` transport
+    backtrace: Backtrace,
+    /// the endpoint to send the evaluation to
+    transport: Transport,
+    /// the expression to evaluate
+    expression: String,
+}
+
+impl Request {
+    /// Returns a new `RequestBuilder` instance configured with an expression
+    /// to evaluate
+    pub fn builder<E>(expression: E) -> RequestBuilder
+    where
+        E: Into<String>,
+    {
+        RequestBuilder::default().expression(expression).clone()
+    }
+
+    /// Returns the program printing the expression's value
+    pub fn program(&self) -> Program {
+        Program::wrap(&self.expression)
+    }
+
+    /// Indicates if the request is sent to the legacy `/evaluate.json`
+    /// endpoint
+    pub(crate) fn legacy(&self) -> bool {
+        self.transport == Transport::Evaluate
+    }
+
+    /// Fills in options left unset with the client's defaults
+    pub(crate) fn merge(&mut self, defaults: &Defaults) {
+        if self.channel.is_none() {
+            self.channel = defaults.channel.clone();
+        }
+        if self.mode.is_none() {
+            self.mode = defaults.mode.clone();
+        }
+        if self.edition.is_none() {
+            self.edition = defaults.edition.clone();
+        }
+    }
+
+    /// Returns the request executing a program
+    pub(crate) fn execute_request(&self, program: &Program) -> ExecuteRequest {
+        ExecuteRequest::builder(program.code.as_str())
+            .channel(self.channel.clone())
+            .mode(self.mode.clone())
+            .edition(self.edition.clone())
+            .backtrace(self.backtrace.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// Returns the `/evaluate.json` request for a program
+    pub(crate) fn legacy_request(&self, program: &Program) -> LegacyRequest {
+        LegacyRequest {
+            version: self.channel.clone().unwrap_or_default(),
+            optimize: match self.mode {
+                Some(Mode::Release) => "3",
+                _ => "0",
+            },
+            code: program.code.clone(),
+            edition: self.edition.clone(),
+            tests: false,
+        }
+    }
+}
+
+// https://github.com/integer32llc/rust-playground/blob/4a49170ea46c4bae244a32b7e460534b56ccf02c/ui/src/main.rs#L580-L593
+/// Legacy `/evaluate.json` request
+#[derive(Debug, Serialize)]
+pub(crate) struct LegacyRequest {
+    version: Channel,
+    optimize: &'static str,
+    code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edition: Option<Edition>,
+    tests: bool,
+}
+
+/// Legacy `/evaluate.json` response
+///
+/// `result` holds stdout on success, and stderr followed by stdout, also
+/// found in `error`, on failure
+#[derive(Debug, Deserialize)]
+pub(crate) struct LegacyResponse {
+    result: String,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// A program wrapping an expression in a `fn main` which prints its value
+/// with `{:?}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The program's source code
+    pub code: String,
+    /// Number of lines of the expression
+    lines: usize,
+}
+
+impl Program {
+    /// Wraps an expression, or a block of statements ending in one
+    ///
+    /// The expression is left unindented so that its columns are unchanged.
+    pub fn wrap(expression: &str) -> Self {
+        let expression = expression.trim_end();
+        Program {
+            code: format!(
+                "fn main() {{\n    let value = {{\n{}\n    }};\n    \
+                 println!(\"{{:?}}\", value);\n}}\n",
+                expression
+            ),
+            lines: expression.lines().count().max(1),
+        }
+    }
+
+    /// Maps the `main.rs` lines of diagnostics and panic locations back to
+    /// the lines of the expression
+    ///
+    /// Lines of the wrapper, such as the `println!` failing for values which
+    /// aren't `Debug`, map to the expression's nearest line. Line number
+    /// gutters of code snippets are remapped as well.
+    pub fn remap(&self, output: &str) -> String {
+        let mut out = String::with_capacity(output.len());
+        for line in output.lines() {
+            out.push_str(&self.remap_gutter(self.remap_locations(line)));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns the expression line of a program line
+    fn line(&self, line: usize) -> usize {
+        line.saturating_sub(PRELUDE).max(1).min(self.lines)
+    }
+
+    /// Remaps `main.rs:line` locations, leaving those of other files, such
+    /// as `domain.rs:5`, as is
+    fn remap_locations(&self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(idx) = rest.find("main.rs:") {
+            let (before, after) = rest.split_at(idx + 8);
+            out.push_str(before);
+            // `main.rs` must start the line or a path segment
+            let boundary = out[..out.len() - 8]
+                .chars()
+                .next_back()
+                .map_or(true, |c| c == '/' || c.is_whitespace());
+            let end =
+                after.find(|c: char| !c.is_digit(10)).unwrap_or(after.len());
+            match after[..end].parse() {
+                Ok(number) if boundary => {
+                    out.push_str(&self.line(number).to_string())
+                }
+                _ => out.push_str(&after[..end]),
+            }
+            rest = &after[end..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Remaps the line number of a `12 |` snippet gutter, keeping its width
+    fn remap_gutter(&self, line: String) -> String {
+        let number = line.trim_start();
+        let end = number
+            .find(|c: char| !c.is_digit(10))
+            .unwrap_or(number.len());
+        if end == 0 || !number[end..].starts_with(" |") {
+            return line;
+        }
+        let width = line.len() - number.len() + end;
+        let remapped = self.line(number[..end].parse().unwrap_or(0));
+        format!("{:>width$}{}", remapped, &number[end..], width = width)
+    }
+
+    /// Returns the evaluation of an `/execute` response
+    pub(crate) fn execute_response(
+        &self,
+        response: execute::Response,
+    ) -> Response {
+        Response {
+            success: response.success,
+            stderr: self.remap(&response.stderr),
+            stdout: response.stdout,
+        }
+    }
+
+    /// Returns the evaluation of an `/evaluate.json` response
+    pub(crate) fn legacy_response(&self, response: LegacyResponse) -> Response {
+        match response.error {
+            Some(error) => Response {
+                success: false,
+                stdout: String::new(),
+                stderr: self.remap(&error),
+            },
+            _ => Response {
+                success: true,
+                stdout: response.result,
+                stderr: String::new(),
+            },
+        }
+    }
+}
+
+/// Evaluation response
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Response {
+    /// Indicates if the expression evaluated successfully
+    pub success: bool,
+    /// Output of the program, ending with the expression's value
+    pub stdout: String,
+    /// Build and program errors, with locations in terms of the expression
+    pub stderr: String,
+}
+
+impl Response {
+    /// Returns the printed value of a successful evaluation
+    pub fn value(&self) -> Option<&str> {
+        if self.success {
+            self.stdout.lines().last()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_expressions() {
+        let program = Program::wrap("let x = 1;\nx + 1\n");
+        assert_eq!(
+            program.code,
+            "fn main() {\n    let value = {\nlet x = 1;\nx + 1\n    };\n    \
+             println!(\"{:?}\", value);\n}\n"
+        );
+    }
+
+    #[test]
+    fn remaps_diagnostics() {
+        let program = Program::wrap("let x = 1;\nx + y");
+        let stderr = "error[E0425]: cannot find value `y` in this scope
+ --> src/main.rs:4:5
+  |
+4 | x + y
+  |     ^ not found in this scope
+";
+        assert_eq!(
+            program.remap(stderr),
+            "error[E0425]: cannot find value `y` in this scope
+ --> src/main.rs:2:5
+  |
+2 | x + y
+  |     ^ not found in this scope
+"
+        );
+        assert_eq!(
+            program.remap("thread 'main' panicked at src/main.rs:6:5:"),
+            "thread 'main' panicked at src/main.rs:2:5:\n"
+        );
+        assert_eq!(
+            program.remap("main.rs:6 src/domain.rs:6 src/not_main.rs:6"),
+            "main.rs:2 src/domain.rs:6 src/not_main.rs:6\n"
+        );
+    }
+
+    #[test]
+    fn legacy_requests_use_optimize_levels() {
+        let request = Request::builder("1 + 1")
+            .mode(Mode::Release)
+            .transport(Transport::Evaluate)
+            .build()
+            .unwrap();
+        assert!(request.legacy());
+        let legacy = request.legacy_request(&request.program());
+        assert_eq!(::serde_json::to_value(&legacy).unwrap()["optimize"], "3");
+    }
+}
@@ -3,24 +3,52 @@
 // https://github.com/colin-kiegel/rust-derive-builder/issues/104
 #![allow(unused_mut)]
 
-use {Channel, CrateType, Mode};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use libtest::TestReport;
+use panic::Panic;
+use validation::{self, ValidationError};
+use {Backtrace, Channel, CrateType, Defaults, Edition, Error, Mode, OptLevel};
 
 // https://github.com/integer32llc/rust-playground/blob/4a49170ea46c4bae244a32b7e460534b56ccf02c/ui/src/main.rs#L551-L563
 /// Parameters for compiling rustlang code
-#[derive(Debug, Serialize, Default, Builder, PartialEq)]
+///
+/// `rustc_args`, `cfgs` and `env` are only sent when set. They're meant for
+/// backends which accept them: the public playground ignores them, so
+/// requests setting them fail validation when sent to it.
+#[derive(Debug, Serialize, Deserialize, Default, Builder, PartialEq, Clone)]
 #[builder(setter(into), default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
 pub struct Request {
-    /// rust release channel
-    channel: Channel,
-    /// compilation mode
-    mode: Mode,
+    /// rust release channel, defaults to the client's channel
+    #[serde(serialize_with = "::or_default")]
+    channel: Option<Channel>,
+    /// compilation mode, defaults to the client's mode
+    #[serde(serialize_with = "::or_default")]
+    mode: Option<Mode>,
+    /// rust edition, defaults to the client's edition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edition: Option<Edition>,
     /// crate type
     crate_type: CrateType,
     /// contains tests
     tests: bool,
-    #[serde(default)]
-    backtrace: bool,
+    /// backtraces for panics
+    backtrace: Backtrace,
+    /// optimization level, setting the mode where unset
+    #[serde(skip_serializing)]
+    opt_level: Option<OptLevel>,
+    /// extra rustc arguments, such as `-C target-cpu=native`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rustc_args: Vec<String>,
+    /// configuration flags, passed to rustc as `--cfg`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cfgs: Vec<String>,
+    /// environment variables for the compiled program
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
     /// source code
     code: String,
 }
@@ -33,11 +61,61 @@ impl Request {
     {
         RequestBuilder::default().code(code).clone()
     }
+
+    /// Returns a copy of this request for another channel and, optionally,
+    /// another mode
+    pub(crate) fn variant(&self, channel: Channel, mode: Option<Mode>) -> Self {
+        Request {
+            channel: Some(channel),
+            mode: mode.or_else(|| self.mode.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Checks for option combinations the playground can't serve
+    ///
+    /// rustc arguments, cfgs and environment variables are only checked
+    /// loosely: the `Client` rejects them for the public playground and
+    /// sends them to any other host unchecked, trusting it to honor them.
+    /// There is no local execution which would honor them fully.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validation::opt_level(&self.opt_level, &self.mode)?;
+        validation::features(&self.channel, &self.code)?;
+        validation::tests(self.tests, &self.crate_type, &self.code)
+    }
+
+    /// Replaces the source code
+    pub(crate) fn set_code(&mut self, code: String) {
+        self.code = code;
+    }
+
+    /// Indicates if rustc arguments, cfgs or environment variables are set
+    pub(crate) fn rustc_options(&self) -> bool {
+        !self.rustc_args.is_empty()
+            || !self.cfgs.is_empty()
+            || !self.env.is_empty()
+    }
+
+    /// Fills in options left unset with the client's defaults
+    pub(crate) fn merge(&mut self, defaults: &Defaults) {
+        if self.mode.is_none() {
+            self.mode = self.opt_level.as_ref().and_then(OptLevel::mode);
+        }
+        if self.channel.is_none() {
+            self.channel = defaults.channel.clone();
+        }
+        if self.mode.is_none() {
+            self.mode = defaults.mode.clone();
+        }
+        if self.edition.is_none() {
+            self.edition = defaults.edition.clone();
+        }
+    }
 }
 
 // https://github.com/integer32llc/rust-playground/blob/4a49170ea46c4bae244a32b7e460534b56ccf02c/ui/src/main.rs#L565-L570
 /// Execute operation response
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
     /// Indicates if request was successful or not
     pub success: bool,
@@ -47,6 +125,195 @@ pub struct Response {
     pub stderr: String,
 }
 
+impl Response {
+    /// Splits stderr after cargo's `Running` line, which separates the build
+    /// output from the program's
+    fn split_stderr(&self) -> (&str, &str) {
+        let mut offset = 0;
+        for line in self.stderr.split_inclusive('\n') {
+            offset += line.len();
+            if line.trim_start().starts_with("Running ") {
+                return self.stderr.split_at(offset);
+            }
+        }
+        // the program never ran
+        (&self.stderr, "")
+    }
+
+    /// Cargo and rustc output, such as warnings and compile errors
+    pub fn build_stderr(&self) -> &str {
+        self.split_stderr().0
+    }
+
+    /// Output the program wrote to stdout
+    pub fn program_stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Output the program wrote to stderr, such as panic messages
+    pub fn program_stderr(&self) -> &str {
+        self.split_stderr().1
+    }
+
+    /// Returns how the program ended
+    pub fn outcome(&self) -> Outcome {
+        if self.success {
+            return Outcome::Ok;
+        }
+        let (build, program) = self.split_stderr();
+        // the playground kills programs running too long with `timeout`
+        if self.stderr.lines().any(|line| {
+            line.contains("Killed") && line.contains("timeout --signal=KILL")
+        }) {
+            return Outcome::TimedOut;
+        }
+        if program.is_empty()
+            && build.lines().any(|line| {
+                line.starts_with("error:") || line.starts_with("error[")
+            })
+        {
+            return Outcome::CompileError;
+        }
+        if let Some(panic) = Panic::parse(program) {
+            return Outcome::Panicked {
+                location: panic.location(),
+                message: panic.message,
+            };
+        }
+        if program.contains("(signal: ") {
+            return Outcome::Signaled;
+        }
+        match exit_code(program) {
+            Some(code) => Outcome::Exited(code),
+            _ if program.is_empty() => Outcome::CompileError,
+            _ => Outcome::Unknown,
+        }
+    }
+
+    /// Returns the response if the program ran successfully, or its outcome
+    /// as an `Error::Program` otherwise
+    pub fn into_result(self) -> Result<Self, Error> {
+        match self.outcome() {
+            Outcome::Ok => Ok(self),
+            outcome => Err(Error::Program(outcome)),
+        }
+    }
+
+    /// Parses the program's panic, backtrace included when `backtrace` was
+    /// enabled
+    pub fn panic(&self) -> Option<Panic> {
+        Panic::parse(self.program_stderr())
+    }
+
+    /// Parses the test harness output of a request with `tests` enabled
+    pub fn test_report(&self) -> Option<TestReport> {
+        TestReport::parse(&self.stdout)
+    }
+
+    /// Parses metadata from cargo's build output
+    pub fn build_info(&self) -> BuildInfo {
+        let mut info = BuildInfo::default();
+        for line in self.build_stderr().lines() {
+            let line = line.trim();
+            if line.starts_with("Finished ") {
+                info.profile = line["Finished ".len()..]
+                    .split_whitespace()
+                    .next()
+                    .map(|profile| profile.trim_matches('`').to_owned());
+                info.duration = line
+                    .rsplit(" in ")
+                    .next()
+                    .filter(|secs| secs.ends_with('s'))
+                    .and_then(|secs| secs[..secs.len() - 1].parse::<f64>().ok())
+                    .map(|secs| {
+                        Duration::from_millis((secs * 1000.0).round() as u64)
+                    });
+            } else if (line.starts_with("warning:")
+                || line.starts_with("warning["))
+                && !line.contains(" generated ")
+            {
+                info.warnings += 1;
+            }
+        }
+        info
+    }
+}
+
+/// How an executed program ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The program ran successfully
+    Ok,
+    /// The program failed to compile
+    CompileError,
+    /// The program panicked
+    Panicked {
+        /// The panic message
+        message: String,
+        /// The source location of the panic, as in `src/main.rs:3:5`
+        location: Option<String>,
+    },
+    /// The program exited with a non-zero code
+    Exited(i32),
+    /// The program was killed by a signal
+    Signaled,
+    /// The program was killed for running longer than the playground allows
+    TimedOut,
+    /// The request failed in a way not recognized, such as an error of the
+    /// playground itself
+    Unknown,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Outcome::Ok => write!(f, "program ran successfully"),
+            Outcome::CompileError => write!(f, "program failed to compile"),
+            Outcome::Panicked {
+                ref message,
+                location: Some(ref location),
+            } => write!(f, "program panicked at {}: {}", location, message),
+            Outcome::Panicked { ref message, .. } => {
+                write!(f, "program panicked: {}", message)
+            }
+            Outcome::Exited(code) => {
+                write!(f, "program exited with code {}", code)
+            }
+            Outcome::Signaled => write!(f, "program was killed by a signal"),
+            Outcome::TimedOut => write!(f, "program timed out"),
+            Outcome::Unknown => {
+                write!(f, "request failed for an unknown reason")
+            }
+        }
+    }
+}
+
+/// Returns the code of cargo's `process didn't exit successfully` line
+fn exit_code(stderr: &str) -> Option<i32> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("process didn't exit successfully"))
+        .filter_map(|line| {
+            let idx = line
+                .find("(exit code: ")
+                .or_else(|| line.find("(exit status: "))?;
+            let code = &line[idx..];
+            code[code.find(": ")? + 2..code.find(')')?].parse().ok()
+        })
+        .next()
+}
+
+/// Metadata about the build of an executed program
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BuildInfo {
+    /// How long cargo took to build the program
+    pub duration: Option<Duration>,
+    /// Number of warnings emitted by the build
+    pub warnings: usize,
+    /// The cargo profile the program was built with, such as `dev`
+    pub profile: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,13 +323,247 @@ mod tests {
         assert_eq!(
             Request::builder("foo").build().unwrap(),
             Request {
-                channel: Channel::Stable,
-                mode: Mode::Debug,
+                channel: None,
+                mode: None,
+                edition: None,
                 crate_type: CrateType::Binary,
                 tests: false,
-                backtrace: false,
+                backtrace: Backtrace::Auto,
+                opt_level: None,
+                rustc_args: Vec::new(),
+                cfgs: Vec::new(),
+                env: BTreeMap::new(),
                 code: String::from("foo"),
             }
         )
     }
+
+    #[test]
+    fn execute_merge_fills_unset_options() {
+        let mut request =
+            Request::builder("foo").mode(Mode::Debug).build().unwrap();
+        request.merge(&Defaults {
+            channel: Some(Channel::Nightly),
+            mode: Some(Mode::Release),
+            edition: None,
+        });
+        assert_eq!(request.channel, Some(Channel::Nightly));
+        assert_eq!(request.mode, Some(Mode::Debug));
+        assert_eq!(request.edition, None);
+    }
+
+    #[test]
+    fn opt_level_sets_unset_mode() {
+        let mut request = Request::builder("foo")
+            .opt_level(OptLevel::O3)
+            .backtrace(true)
+            .build()
+            .unwrap();
+        request.merge(&Defaults::default());
+        assert_eq!(request.mode, Some(Mode::Release));
+        assert_eq!(request.validate(), Ok(()));
+        let json = ::serde_json::to_value(&request).unwrap();
+        assert_eq!(json["backtrace"], true);
+        assert!(json.get("optLevel").is_none());
+    }
+
+    #[test]
+    fn validates_feature_gates() {
+        let code = "#![feature(test)]\nfn main() {}";
+        let request = Request::builder(code).build().unwrap();
+        assert_eq!(
+            request.validate(),
+            Err(ValidationError::FeatureRequiresNightly)
+        );
+        let request = Request::builder(code)
+            .channel(Channel::Nightly)
+            .build()
+            .unwrap();
+        assert_eq!(request.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_rustc_options_on_the_public_playground() {
+        let request = Request::builder("fn main() {}")
+            .cfgs(vec!["foo".to_string()])
+            .build()
+            .unwrap();
+        assert!(request.rustc_options());
+        let public = "https://play.rust-lang.org".parse().unwrap();
+        let private = "https://playground.example.com".parse().unwrap();
+        assert_eq!(
+            validation::rustc_options(&public, request.rustc_options()),
+            Err(ValidationError::UnsupportedRustcOptions)
+        );
+        assert_eq!(
+            validation::rustc_options(&private, request.rustc_options()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validates_opt_levels() {
+        let request = Request::builder("foo")
+            .opt_level(OptLevel::O2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.validate(),
+            Err(ValidationError::UnsupportedOptLevel(OptLevel::O2))
+        );
+        let request = Request::builder("foo")
+            .opt_level(OptLevel::O0)
+            .mode(Mode::Release)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.validate(),
+            Err(ValidationError::OptLevelConflictsWithMode(
+                OptLevel::O0,
+                Mode::Release
+            ))
+        );
+    }
+
+    #[test]
+    fn extra_options_serialize_when_set() {
+        let request = Request::builder("foo").build().unwrap();
+        let json = ::serde_json::to_value(&request).unwrap();
+        assert!(json.get("rustcArgs").is_none());
+        assert!(json.get("env").is_none());
+        let mut env = BTreeMap::new();
+        env.insert("RUST_LOG".to_string(), "debug".to_string());
+        let request = Request::builder("foo")
+            .rustc_args(vec!["-C".to_string(), "target-cpu=native".into()])
+            .cfgs(vec!["feature=\"x\"".to_string()])
+            .env(env)
+            .build()
+            .unwrap();
+        let json = ::serde_json::to_value(&request).unwrap();
+        assert_eq!(json["rustcArgs"][1], "target-cpu=native");
+        assert_eq!(json["cfgs"][0], "feature=\"x\"");
+        assert_eq!(json["env"]["RUST_LOG"], "debug");
+    }
+
+    #[test]
+    fn splits_build_and_program_output() {
+        let response = Response {
+            success: false,
+            stdout: "hello\n".into(),
+            stderr: "   Compiling playground v0.0.1 (/playground)
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+warning: `playground` (bin \"playground\") generated 1 warning
+    Finished dev [unoptimized + debuginfo] target(s) in 1.25s
+     Running `target/debug/playground`
+thread 'main' panicked at 'boom', src/main.rs:3:5
+"
+            .into(),
+        };
+        assert!(response.build_stderr().ends_with("playground`\n"));
+        assert_eq!(
+            response.program_stderr(),
+            "thread 'main' panicked at 'boom', src/main.rs:3:5\n"
+        );
+        assert_eq!(response.program_stdout(), "hello\n");
+        assert_eq!(
+            response.build_info(),
+            BuildInfo {
+                duration: Some(Duration::from_millis(1250)),
+                warnings: 1,
+                profile: Some("dev".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn build_failures_have_no_program_output() {
+        let response = Response {
+            success: false,
+            stdout: String::new(),
+            stderr: "   Compiling playground v0.0.1 (/playground)\n\
+                     error[E0425]: cannot find value `y` in this scope\n"
+                .into(),
+        };
+        assert_eq!(response.build_stderr(), response.stderr);
+        assert_eq!(response.program_stderr(), "");
+        assert_eq!(response.build_info().duration, None);
+    }
+
+    fn failed(stderr: &str) -> Response {
+        Response {
+            success: false,
+            stdout: String::new(),
+            stderr: format!(
+                "    Finished dev [unoptimized + debuginfo] target(s) in 0.5s\n\
+                 \x20    Running `target/debug/playground`\n{}",
+                stderr
+            ),
+        }
+    }
+
+    #[test]
+    fn outcomes_distinguish_failures() {
+        assert_eq!(
+            failed("thread 'main' panicked at 'boom', src/main.rs:3:5\n")
+                .outcome(),
+            Outcome::Panicked {
+                message: "boom".into(),
+                location: Some("src/main.rs:3:5".into()),
+            }
+        );
+        assert_eq!(
+            failed(
+                "thread 'main' panicked at src/main.rs:3:5:\nboom\n\
+                 note: run with `RUST_BACKTRACE=1`\n"
+            )
+            .outcome(),
+            Outcome::Panicked {
+                message: "boom".into(),
+                location: Some("src/main.rs:3:5".into()),
+            }
+        );
+        assert_eq!(
+            failed(
+                "error: process didn't exit successfully: \
+                 `target/debug/playground` (exit status: 3)\n"
+            )
+            .outcome(),
+            Outcome::Exited(3)
+        );
+        assert_eq!(
+            failed(
+                "error: process didn't exit successfully: \
+                 `target/debug/playground` (signal: 11, SIGSEGV)\n"
+            )
+            .outcome(),
+            Outcome::Signaled
+        );
+        assert_eq!(
+            failed(
+                "/playground/tools/entrypoint.sh: line 11:     7 Killed  \
+                 timeout --signal=KILL ${timeout} \"$@\"\n"
+            )
+            .outcome(),
+            Outcome::TimedOut
+        );
+        assert_eq!(
+            failed("internal error: container unavailable\n").outcome(),
+            Outcome::Unknown
+        );
+    }
+
+    #[test]
+    fn into_result_fails_with_outcome() {
+        let response = Response {
+            success: false,
+            stdout: String::new(),
+            stderr: "error[E0425]: cannot find value `y` in this scope\n"
+                .into(),
+        };
+        match response.into_result() {
+            Err(Error::Program(Outcome::CompileError)) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
 }
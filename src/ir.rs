@@ -0,0 +1,251 @@
+//! LLVM IR and MIR navigation interfaces
+
+use asm::demangle;
+
+/// A function, or other item with a body, of LLVM IR or MIR output
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The demangled function name
+    pub name: String,
+    /// The function's source, from its signature to its closing brace
+    pub code: String,
+}
+
+impl Function {
+    /// Indicates if the function is named `name`, either fully or by its
+    /// last path segments, such as `sum` or `example::sum` for `example::sum`
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.name.ends_with(&format!("::{}", name))
+    }
+}
+
+/// LLVM IR or MIR output split into sections
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Module {
+    /// Function definitions, in order
+    pub functions: Vec<Function>,
+    /// External function declarations
+    pub declarations: Vec<String>,
+    /// Metadata and attribute group definitions
+    pub metadata: Vec<String>,
+    /// Remaining top level lines, such as globals and type definitions
+    pub other: Vec<String>,
+}
+
+impl Module {
+    /// Splits `Target::Llvm` output into its sections
+    pub fn llvm(ir: &str) -> Self {
+        let mut module = Module::default();
+        let mut lines = ir.lines();
+        while let Some(line) = lines.next() {
+            if line.starts_with("define ") {
+                let name = llvm_name(line);
+                module.functions.push(body(name, line, &mut lines));
+            } else if line.starts_with("declare ") {
+                module.declarations.push(line.to_owned());
+            } else if line.starts_with('!') || line.starts_with("attributes ")
+            {
+                module.metadata.push(line.to_owned());
+            } else if !line.trim().is_empty() {
+                module.other.push(line.to_owned());
+            }
+        }
+        module
+    }
+
+    /// Splits `Target::Mir` output into its sections
+    ///
+    /// MIR bodies of constants, statics and promoted values are listed as
+    /// functions, named as rustc prints them.
+    pub fn mir(mir: &str) -> Self {
+        let mut module = Module::default();
+        let mut lines = mir.lines();
+        while let Some(line) = lines.next() {
+            if !line.starts_with(char::is_whitespace)
+                && line.trim_end().ends_with('{')
+            {
+                let name = mir_name(line);
+                module.functions.push(body(name, line, &mut lines));
+            } else if !line.trim().is_empty() && !line.starts_with("//") {
+                module.other.push(line.to_owned());
+            }
+        }
+        module
+    }
+}
+
+/// Collects an item from its opening line to the closing brace in the first
+/// column
+fn body<'a, I>(name: String, first: &str, lines: &mut I) -> Function
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut code = format!("{}\n", first);
+    for line in lines {
+        code.push_str(line);
+        code.push('\n');
+        if line.starts_with('}') {
+            break;
+        }
+    }
+    Function { name, code }
+}
+
+/// Returns the demangled name of an LLVM `define` line's function
+fn llvm_name(line: &str) -> String {
+    let name = match line.find('@') {
+        Some(idx) => &line[idx + 1..],
+        _ => return String::new(),
+    };
+    let name = if name.starts_with('"') {
+        name[1..].split('"').next().unwrap_or_default()
+    } else {
+        name.split('(').next().unwrap_or_default()
+    };
+    demangle(name)
+}
+
+/// Returns the name of a MIR item from its opening line, such as `sum` for
+/// `fn sum(_1: i32) -> i32 {`
+///
+/// Methods keep the path of their impl, as in `<impl at src/main.rs:3:1>::fmt`.
+fn mir_name(line: &str) -> String {
+    let line = line.trim_end().trim_end_matches('{').trim_end();
+    if line.starts_with("fn ") {
+        let name = &line[3..];
+        // the parameters start at the first parenthesis outside of `<...>`
+        let mut depth = 0i32;
+        let end = name
+            .find(|c: char| {
+                match c {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    _ => (),
+                }
+                c == '(' && depth == 0
+            })
+            .unwrap_or(name.len());
+        return name[..end].trim().to_owned();
+    }
+    let line = ["const ", "static mut ", "static "]
+        .iter()
+        .find(|keyword| line.starts_with(*keyword))
+        .map_or(line, |keyword| &line[keyword.len()..]);
+    match line.find(": ") {
+        Some(idx) => line[..idx].to_owned(),
+        _ => line.to_owned(),
+    }
+}
+
+/// Removes debug information from LLVM IR or MIR
+///
+/// `!dbg` attachments, `llvm.dbg` intrinsic calls, `#dbg_` records and MIR
+/// `debug` statements are dropped.
+pub fn strip_debug(code: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#dbg_")
+            || trimmed.starts_with("debug ")
+            || trimmed.starts_with("declare void @llvm.dbg.")
+            || trimmed.starts_with("call void @llvm.dbg.")
+            || trimmed.starts_with("tail call void @llvm.dbg.")
+        {
+            continue;
+        }
+        out.push_str(&strip_dbg_attachments(line));
+        out.push('\n');
+    }
+    out
+}
+
+/// Removes the `!dbg !N` attachments of an LLVM IR line
+fn strip_dbg_attachments(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(idx) = rest.find("!dbg !") {
+        let before = rest[..idx].trim_end();
+        out.push_str(before.trim_end_matches(','));
+        let after = &rest[idx + 6..];
+        rest = &after[after
+            .find(|c: char| !c.is_alphanumeric() && c != '.' && c != '_')
+            .unwrap_or(after.len())..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_llvm_ir() {
+        let ir = r#"; ModuleID = 'playground.3a1fbbbb-cgu.0'
+source_filename = "playground.3a1fbbbb-cgu.0"
+
+; playground::sum
+define i32 @_ZN10playground3sum17h0123456789abcdefE(i32 %a) unnamed_addr #0 !dbg !5 {
+start:
+  call void @llvm.dbg.declare(metadata i32* %a, metadata !12, metadata !DIExpression()), !dbg !13
+  ret i32 %a, !dbg !14
+}
+
+declare void @llvm.dbg.declare(metadata, metadata, metadata) #1
+
+attributes #0 = { uwtable }
+!5 = distinct !DISubprogram(name: "sum")
+"#;
+        let module = Module::llvm(ir);
+        assert_eq!(module.functions.len(), 1);
+        assert_eq!(module.functions[0].name, "playground::sum");
+        assert!(module.functions[0].matches("sum"));
+        assert_eq!(module.declarations.len(), 1);
+        assert_eq!(module.metadata.len(), 2);
+        assert_eq!(
+            strip_debug(&module.functions[0].code),
+            "define i32 @_ZN10playground3sum17h0123456789abcdefE(i32 %a) \
+             unnamed_addr #0 {\nstart:\n  ret i32 %a\n}\n"
+        );
+    }
+
+    #[test]
+    fn splits_mir() {
+        let mir = r#"// WARNING: This output format is intended for human consumers only
+fn sum(_1: i32, _2: i32) -> i32 {
+    debug a => _1;
+    let mut _0: i32;
+
+    bb0: {
+        _0 = Add(_1, _2);
+        return;
+    }
+}
+
+const FOO: i32 = {
+    let mut _0: i32;
+}
+"#;
+        let module = Module::mir(mir);
+        let names = module
+            .functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["sum", "FOO"]);
+        assert!(module.functions[0].code.ends_with("    }\n}\n"));
+        assert!(!strip_debug(&module.functions[0].code).contains("debug a"));
+    }
+
+    #[test]
+    fn names_mir_impl_methods() {
+        let mir = "fn <impl at src/main.rs:3:1>::fmt(_1: &Foo, _2: &mut \
+                   Formatter<'_>) -> Result<(), std::fmt::Error> {\n\
+                   \x20   bb0: {\n        return;\n    }\n}\n\
+                   fn <impl Fn(i32) for Bar>::call(_1: &Bar) {\n}\n";
+        let module = Module::mir(mir);
+        assert_eq!(module.functions[0].name, "<impl at src/main.rs:3:1>::fmt");
+        assert!(module.functions[0].matches("fmt"));
+        assert_eq!(module.functions[1].name, "<impl Fn(i32) for Bar>::call");
+    }
+}
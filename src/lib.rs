@@ -6,19 +6,21 @@
 //! // recess interfaces
 //! extern crate recess;
 //! // tokio async io
-//! extern crate tokio_core;
+//! extern crate tokio;
 //! // futures combinators
 //! extern crate futures;
 //!
-//! use recess::{Client, CompileRequest};
+//! use recess::{Channel, Client, CompileRequest};
 //! use futures::Future;
-//! use tokio_core::reactor::Core;
+//! use tokio::runtime::Runtime;
 //!
 //! fn main() {
-//!   let mut core = Core::new().unwrap();
-//!   let client = Client::new(
-//!      &core.handle(),
-//!   );
+//!   let mut runtime = Runtime::new().unwrap();
+//!   let client = Client::builder()
+//!       .user_agent("my-app/1.0")
+//!       .channel(Channel::Nightly)
+//!       .build()
+//!       .unwrap();
 //!
 //!   let work = client.compile(CompileRequest::builder(
 //!              r#"fn main() { println!("{}", 1); }"#
@@ -30,14 +32,14 @@
 //!            Ok(())
 //!        });
 //!
-//!   println!("{:#?}", core.run(work))
+//!   println!("{:#?}", runtime.block_on(work))
 //! }
 //! ```
 //!
 //! # Cargo features
 //!
-//! This crate has one Cargo feature, `tls`, which adds HTTPS support via the `Client::new`
-//! constructor. This feature is enabled by default.
+//! This crate has one Cargo feature, `tls`, which adds HTTPS and proxy support via the
+//! `Client::new` and `Client::builder` constructors. This feature is enabled by default.
 #![warn(missing_docs)]
 
 #[macro_use]
@@ -50,39 +52,84 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 #[cfg(feature = "tls")]
+extern crate hyper_proxy;
+#[cfg(feature = "tls")]
 extern crate hyper_tls;
+#[cfg(feature = "tls")]
+extern crate native_tls;
 extern crate serde_json;
+extern crate serde_yaml;
 extern crate tokio_core;
+extern crate tokio_timer;
+extern crate toml;
+#[cfg(feature = "tls")]
+extern crate typed_headers;
 extern crate url;
+extern crate wasmprinter;
+extern crate wat;
 
+use std::fmt;
 use std::str::FromStr;
+use std::time::Duration;
 
+use futures::future;
+use futures::stream;
 use futures::Future as StdFuture;
 use futures::Stream;
 use hyper::client::connect::Connect;
-use hyper::client::HttpConnector;
+use hyper::header::HeaderMap;
 use hyper::{Body, Method, Request, Uri};
-//use hyper::header::ContentType;
-#[cfg(feature = "tls")]
-use hyper_tls::HttpsConnector;
-use serde::de::DeserializeOwned;
-use serde::ser::Serialize;
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use tokio_timer::Timer;
 
+pub mod asm;
+pub mod batch;
+pub mod case;
 pub mod clippy;
+pub mod compare;
 pub mod compile;
+pub mod config;
+pub mod diff;
+pub mod directive;
+pub mod doctest;
+pub mod eval;
 pub mod execute;
 pub mod format;
+pub mod ir;
+pub mod libtest;
 pub mod lint;
+pub mod markdown;
+pub mod panic;
+pub mod project;
+pub mod repl;
+pub mod snippet;
 
+pub use case::Case;
 pub use clippy::Request as ClippyRequest;
 pub use compile::Request as CompileRequest;
+pub use eval::Request as EvalRequest;
 pub use execute::Request as ExecuteRequest;
 pub use format::Request as FormatRequest;
 pub use lint::Request as LintRequest;
+pub use project::Project;
 
 mod error;
 pub use error::*;
 
+mod validation;
+pub use validation::ValidationError;
+
+mod limit;
+use limit::Throttle;
+
+#[cfg(feature = "tls")]
+mod builder;
+#[cfg(feature = "tls")]
+pub use builder::{ClientBuilder, Connector, DEFAULT_HOST, DEFAULT_USER_AGENT};
+#[cfg(feature = "tls")]
+pub use native_tls::Certificate;
+
 #[derive(Debug, Deserialize, PartialEq)]
 struct ClientError {
     pub error: String,
@@ -91,10 +138,30 @@ struct ClientError {
 /// A type alias for futures that may return recess::Error's
 pub type Future<T> = Box<StdFuture<Item = T, Error = Error> + Send>;
 
+/// Serializes unset options as their type's default
+fn or_default<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + Default,
+    S: Serializer,
+{
+    match *value {
+        Some(ref value) => value.serialize(serializer),
+        _ => T::default().serialize(serializer),
+    }
+}
+
+/// Client-wide request options applied to requests which don't set their own
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Defaults {
+    pub channel: Option<Channel>,
+    pub mode: Option<Mode>,
+    pub edition: Option<Edition>,
+}
+
 /// Type of crate
 ///
 /// The `Default` is `Binary`
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum CrateType {
     /// Rust binary
     #[serde(rename = "bin")]
@@ -130,7 +197,7 @@ impl FromStr for CrateType {
 /// Rustc compilation mode.
 ///
 /// The `Default` is `Debug`
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Mode {
     /// Debug compilation mode
     #[serde(rename = "debug")]
@@ -140,6 +207,12 @@ pub enum Mode {
     Release,
 }
 
+impl Mode {
+    pub fn variants() -> &'static [&'static str] {
+        &["debug", "release"]
+    }
+}
+
 impl Default for Mode {
     fn default() -> Self {
         Mode::Debug
@@ -157,10 +230,19 @@ impl FromStr for Mode {
     }
 }
 
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Mode::Debug => "debug",
+            Mode::Release => "release",
+        })
+    }
+}
+
 /// Release train options.
 ///
 /// The `Default` is `Stable`
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Channel {
     /// stable release
@@ -195,10 +277,62 @@ impl FromStr for Channel {
     }
 }
 
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Channel::Stable => "stable",
+            Channel::Beta => "beta",
+            Channel::Nightly => "nightly",
+        })
+    }
+}
+
+/// Rust language edition
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum Edition {
+    /// Rust 2015
+    #[serde(rename = "2015")]
+    Edition2015,
+    /// Rust 2018
+    #[serde(rename = "2018")]
+    Edition2018,
+    /// Rust 2021
+    #[serde(rename = "2021")]
+    Edition2021,
+}
+
+impl Edition {
+    pub fn variants() -> &'static [&'static str] {
+        &["2015", "2018", "2021"]
+    }
+}
+
+impl FromStr for Edition {
+    type Err = &'static str;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "2015" => Ok(Edition::Edition2015),
+            "2018" => Ok(Edition::Edition2018),
+            "2021" => Ok(Edition::Edition2021),
+            _ => Err("invalid edition"),
+        }
+    }
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Edition::Edition2015 => "2015",
+            Edition::Edition2018 => "2018",
+            Edition::Edition2021 => "2021",
+        })
+    }
+}
+
 /// Assembly flavor.
 ///
 /// The `Default` is `Att`
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum AsmFlavor {
     /// AT&T assembly
@@ -207,65 +341,199 @@ pub enum AsmFlavor {
     Intel,
 }
 
+impl AsmFlavor {
+    pub fn variants() -> &'static [&'static str] {
+        &["att", "intel"]
+    }
+}
+
 impl Default for AsmFlavor {
     fn default() -> Self {
         AsmFlavor::Att
     }
 }
 
+impl FromStr for AsmFlavor {
+    type Err = &'static str;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "att" => Ok(AsmFlavor::Att),
+            "intel" => Ok(AsmFlavor::Intel),
+            _ => Err("invalid assembly flavor"),
+        }
+    }
+}
+
 /// Rustc backtrace options
-#[derive(Debug, Serialize)]
+///
+/// The playground only toggles backtraces, so `Auto` leaves them disabled
+/// there. Requests also accept a plain `bool`, as in `backtrace(true)`.
+///
+/// The `Default` is `Auto`
+#[derive(Debug, Clone, PartialEq)]
 pub enum Backtrace {
     /// No backtraces
-    #[serde(rename = "0")]
     Never,
     /// Always return backtraces
-    #[serde(rename = "1")]
     Always,
     /// Detect when to return backtraces
-    #[serde(rename = "2")]
     Auto,
 }
 
+impl Backtrace {
+    pub fn variants() -> &'static [&'static str] {
+        &["auto", "always", "never"]
+    }
+}
+
 impl Default for Backtrace {
     fn default() -> Self {
         Backtrace::Auto
     }
 }
 
+impl From<bool> for Backtrace {
+    fn from(enable: bool) -> Self {
+        if enable {
+            Backtrace::Always
+        } else {
+            Backtrace::Never
+        }
+    }
+}
+
+impl FromStr for Backtrace {
+    type Err = &'static str;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Backtrace::Auto),
+            "always" => Ok(Backtrace::Always),
+            "never" => Ok(Backtrace::Never),
+            _ => Err("invalid backtrace"),
+        }
+    }
+}
+
+// the playground expects a bool
+impl Serialize for Backtrace {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bool(*self == Backtrace::Always)
+    }
+}
+
+impl<'de> Deserialize<'de> for Backtrace {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Flag(bool),
+            Name(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Flag(enable) => Ok(enable.into()),
+            Repr::Name(name) => name.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
 /// Optimization levels for rustc
-#[derive(Debug, Serialize)]
+///
+/// The playground only offers the levels of its modes, `O0` for
+/// `Mode::Debug` and `O3` for `Mode::Release`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum OptLevel {
+    /// No optimizations
     #[serde(rename = "0")]
     O0,
+    /// Basic optimizations
     #[serde(rename = "1")]
     O1,
+    /// Some optimizations
     #[serde(rename = "2")]
     O2,
+    /// All optimizations
     #[serde(rename = "3")]
     O3,
 }
 
+impl OptLevel {
+    pub fn variants() -> &'static [&'static str] {
+        &["0", "1", "2", "3"]
+    }
+
+    /// Returns the playground mode compiling with this level, if any
+    pub fn mode(&self) -> Option<Mode> {
+        match *self {
+            OptLevel::O0 => Some(Mode::Debug),
+            OptLevel::O3 => Some(Mode::Release),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = &'static str;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            _ => Err("invalid opt-level"),
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+            OptLevel::O3 => "3",
+        })
+    }
+}
+
 /// Rust playground client
-pub struct Client<C = HttpsConnector<HttpConnector>>
+///
+/// Clones share the same connection pool and request limits
+#[derive(Clone)]
+pub struct Client<C = Connector>
 where
     C: Connect + Clone + 'static,
 {
     host: Uri,
     http: hyper::Client<C>,
+    headers: HeaderMap,
+    defaults: Defaults,
+    timeout: Option<(Timer, Duration)>,
+    throttle: Throttle,
+    validate: bool,
 }
 
 /// An implementation of Client for HttpsConnectors
 #[cfg(feature = "tls")]
-impl Client<HttpsConnector<HttpConnector>> {
+impl Client<Connector> {
     /// Creates a new instance of a `Client` using a `hyper::Client`
     /// preconfigured for tls.
     ///
-    /// For client customization use `Client::custom` instead
+    /// Panics if the proxy configuration in the environment is invalid.
+    /// For client customization use `Client::builder` instead
     pub fn new() -> Self {
-        let connector = HttpsConnector::new(4).unwrap();
-        let hyper = hyper::Client::builder().keep_alive(true).build(connector);
-        Client::custom("https://play.rust-lang.org".parse().unwrap(), hyper)
+        Client::builder().build().unwrap()
+    }
+
+    /// Returns a new `ClientBuilder` for configuring the host, headers,
+    /// timeouts, default request options, proxies and tls
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
     }
 }
 
@@ -275,70 +543,238 @@ where
 {
     /// Creates a new playground
     pub fn custom(host: Uri, http: hyper::Client<C>) -> Self {
-        Self { host, http }
+        Self {
+            host,
+            http,
+            headers: HeaderMap::new(),
+            defaults: Defaults::default(),
+            timeout: None,
+            throttle: Throttle::default(),
+            validate: true,
+        }
     }
 
     /// Executes rustlang code
-    pub fn execute(&self, req: ExecuteRequest) -> Future<execute::Response> {
-        self.request::<execute::Request, execute::Response>(
-            "https://play.rust-lang.org/execute",
-            req,
+    ///
+    /// Options left unset on the request are taken from the client's defaults
+    pub fn execute(
+        &self,
+        mut req: ExecuteRequest,
+    ) -> Future<execute::Response> {
+        req.merge(&self.defaults);
+        if let Err(err) = self.validate_execute(&req) {
+            return Box::new(future::err(err.into()));
+        }
+        self.request::<execute::Request, execute::Response>("/execute", req)
+    }
+
+    /// Executes a multi-file project, with the options of a request whose
+    /// code is replaced by the project's
+    ///
+    /// Backends accepting multiple files receive the project's files and
+    /// dependencies as is. The public playground only accepts a single file,
+    /// so the project is bundled, and projects with dependencies are an
+    /// error.
+    pub fn execute_project(
+        &self,
+        project: &Project,
+        mut req: ExecuteRequest,
+    ) -> Future<execute::Response> {
+        let code = if validation::is_public(&self.host) {
+            project.bundle()
+        } else {
+            project.bundle_files()
+        };
+        match code {
+            Ok(code) => req.set_code(code),
+            Err(err) => return Box::new(future::err(err)),
+        }
+        if validation::is_public(&self.host) {
+            return self.execute(req);
+        }
+        req.merge(&self.defaults);
+        if let Err(err) = self.validate_execute(&req) {
+            return Box::new(future::err(err.into()));
+        }
+        self.request::<project::Request, execute::Response>(
+            "/execute",
+            project::Request::new(req, project.clone()),
         )
     }
 
+    /// Validates an execute request, unless validation is disabled
+    fn validate_execute(
+        &self,
+        req: &ExecuteRequest,
+    ) -> Result<(), ValidationError> {
+        if !self.validate {
+            return Ok(());
+        }
+        req.validate().and_then(|_| {
+            validation::rustc_options(&self.host, req.rustc_options())
+        })
+    }
+
     /// Compiles rustlang code
-    pub fn compile(&self, req: CompileRequest) -> Future<compile::Response> {
-        self.request::<CompileRequest, compile::Response>(
-            "https://play.rust-lang.org/compile",
-            req,
+    ///
+    /// Options left unset on the request are taken from the client's defaults
+    pub fn compile(
+        &self,
+        mut req: CompileRequest,
+    ) -> Future<compile::Response> {
+        req.merge(&self.defaults);
+        if self.validate {
+            let valid = req.validate().and_then(|_| {
+                validation::rustc_options(&self.host, req.rustc_options())
+            });
+            if let Err(err) = valid {
+                return Box::new(future::err(err.into()));
+            }
+        }
+        self.request::<CompileRequest, compile::Response>("/compile", req)
+    }
+
+    /// Evaluates an expression, printing its value with `{:?}`
+    ///
+    /// Options left unset on the request are taken from the client's defaults
+    pub fn eval(&self, mut req: EvalRequest) -> Future<eval::Response> {
+        req.merge(&self.defaults);
+        let program = req.program();
+        if req.legacy() {
+            let legacy = req.legacy_request(&program);
+            Box::new(
+                self.request::<eval::LegacyRequest, eval::LegacyResponse>(
+                    "/evaluate.json",
+                    legacy,
+                )
+                .map(move |response| program.legacy_response(response)),
+            )
+        } else {
+            Box::new(
+                self.execute(req.execute_request(&program))
+                    .map(move |response| program.execute_response(response)),
+            )
+        }
+    }
+
+    /// Executes a series of requests, with at most `concurrency` in flight
+    ///
+    /// Results are returned in the order of their requests
+    pub fn execute_all<I>(
+        &self,
+        requests: I,
+        concurrency: usize,
+    ) -> Future<Vec<Result<execute::Response, Error>>>
+    where
+        I: IntoIterator<Item = ExecuteRequest>,
+        I::IntoIter: Send + 'static,
+    {
+        let client = self.clone();
+        Box::new(
+            stream::iter_ok(requests)
+                .map(move |req| client.execute(req).then(Ok))
+                .buffered(concurrency.max(1))
+                .collect(),
+        )
+    }
+
+    /// Compiles a series of requests, with at most `concurrency` in flight
+    ///
+    /// Results are returned in the order of their requests
+    pub fn compile_all<I>(
+        &self,
+        requests: I,
+        concurrency: usize,
+    ) -> Future<Vec<Result<compile::Response, Error>>>
+    where
+        I: IntoIterator<Item = CompileRequest>,
+        I::IntoIter: Send + 'static,
+    {
+        let client = self.clone();
+        Box::new(
+            stream::iter_ok(requests)
+                .map(move |req| client.compile(req).then(Ok))
+                .buffered(concurrency.max(1))
+                .collect(),
         )
     }
 
     /// Formats rustlang code
     pub fn format(&self, req: format::Request) -> Future<format::Response> {
-        self.request::<format::Request, format::Response>(
-            "https://play.rust-lang.org/format",
-            req,
-        )
+        self.request::<format::Request, format::Response>("/format", req)
     }
 
     /// Lint rustlang code
     pub fn lint(&self, req: lint::Request) -> Future<lint::Response> {
-        self.request::<lint::Request, lint::Response>(
-            "https://play.rust-lang.org/clippy",
-            req,
-        )
+        self.request::<lint::Request, lint::Response>("/clippy", req)
     }
 
-    fn request<I, O>(&self, url: &str, input: I) -> Future<O>
+    /// Sends a request file's request to its endpoint
+    pub fn run(&self, case: Case) -> Future<case::Response> {
+        match case {
+            Case::Execute(req) => {
+                Box::new(self.execute(req).map(case::Response::Execute))
+            }
+            Case::Compile(req) => {
+                Box::new(self.compile(req).map(case::Response::Compile))
+            }
+            Case::Format(req) => {
+                Box::new(self.format(req).map(case::Response::Format))
+            }
+            Case::Lint(req) => {
+                Box::new(self.lint(req).map(case::Response::Lint))
+            }
+        }
+    }
+
+    fn request<I, O>(&self, path: &str, input: I) -> Future<O>
     where
         I: Serialize,
         O: DeserializeOwned + 'static + Send,
     {
+        let url =
+            format!("{}{}", self.host.to_string().trim_end_matches('/'), path);
         let mut builder = Request::builder();
         builder.method(Method::POST);
-        builder.uri(url);
+        builder.uri(url.as_str());
+        for (name, value) in &self.headers {
+            builder.header(name, value.clone());
+        }
         builder.header("Content-Type", "application/json");
-        //req.headers_mut().set(ContentType::json());
         let req = builder
             .body(Body::from(serde_json::to_vec(&input).unwrap()))
             .unwrap();
-        Box::new(self.http.request(req).map_err(Error::from).and_then(
-            |response| {
-                let status = response.status();
-                let body = response.into_body().concat2().map_err(Error::from);
-                body.and_then(move |body| {
-                    if status.is_success() {
-                        serde_json::from_slice::<O>(&body)
-                            .map_err(|err| Error::Codec(err).into())
-                    } else {
-                        match serde_json::from_slice::<ClientError>(&body) {
-                            Ok(_) => Err(Error::Fault(status).into()),
-                            Err(error) => Err(Error::Codec(error).into()),
+        let http = self.http.clone();
+        let timeout = self.timeout.clone();
+        Box::new(self.throttle.acquire().and_then(move |permit| {
+            let response =
+                http.request(req).map_err(Error::from).and_then(|response| {
+                    let status = response.status();
+                    let body =
+                        response.into_body().concat2().map_err(Error::from);
+                    body.and_then(move |body| {
+                        if status.is_success() {
+                            serde_json::from_slice::<O>(&body)
+                                .map_err(|err| Error::Codec(err).into())
+                        } else {
+                            match serde_json::from_slice::<ClientError>(&body) {
+                                Ok(_) => Err(Error::Fault(status).into()),
+                                Err(error) => Err(Error::Codec(error).into()),
+                            }
                         }
-                    }
-                })
-            },
-        ))
+                    })
+                });
+            let response: Future<O> = match timeout {
+                Some((timer, duration)) => {
+                    Box::new(timer.timeout(response, duration))
+                }
+                _ => Box::new(response),
+            };
+            // hold on to the permit until the request completes
+            response.then(move |result| {
+                drop(permit);
+                result
+            })
+        }))
     }
 }
@@ -0,0 +1,239 @@
+//! Test harness output interfaces
+
+use std::fmt;
+
+use panic::Panic;
+
+/// The status of a single test
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestStatus {
+    /// The test passed
+    Passed,
+    /// The test failed
+    Failed,
+    /// The test was skipped with `#[ignore]`
+    Ignored,
+    /// The benchmark was measured
+    Measured,
+}
+
+/// A single test of a test run
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    /// The test's path, as in `tests::it_works`
+    pub name: String,
+    /// How the test ended
+    pub status: TestStatus,
+    /// Output captured from a failed test
+    pub stdout: String,
+    /// The message of a failed test's panic
+    pub panic_message: Option<String>,
+}
+
+/// The results of a test harness run
+///
+/// Totals are summed over every test binary run, such as unit and doc tests.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TestReport {
+    /// Number of passed tests
+    pub passed: usize,
+    /// Number of failed tests
+    pub failed: usize,
+    /// Number of ignored tests
+    pub ignored: usize,
+    /// Number of measured benchmarks
+    pub measured: usize,
+    /// Number of tests filtered out
+    pub filtered: usize,
+    /// The tests, in the order they completed
+    pub tests: Vec<TestCase>,
+}
+
+impl TestReport {
+    /// Parses the stdout of a test harness run, returning `None` if it holds
+    /// no `test result:` summary
+    pub fn parse(stdout: &str) -> Option<TestReport> {
+        let mut report = TestReport::default();
+        let mut summarized = false;
+        let mut section: Option<(String, Vec<&str>)> = None;
+        for line in stdout.lines() {
+            if let Some(name) = section_header(line) {
+                report.close_section(section.take());
+                section = Some((name.to_owned(), Vec::new()));
+                continue;
+            }
+            if line == "failures:" || line.starts_with("test result: ") {
+                report.close_section(section.take());
+            }
+            if let Some((_, ref mut lines)) = section {
+                lines.push(line);
+            } else if line.starts_with("test result: ") {
+                summarized = true;
+                report.add_totals(&line["test result: ".len()..]);
+            } else if let Some(case) = test_line(line) {
+                report.tests.push(case);
+            }
+        }
+        if summarized {
+            Some(report)
+        } else {
+            None
+        }
+    }
+
+    /// Indicates if no test failed
+    pub fn success(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// Returns the failed tests
+    pub fn failures(&self) -> Vec<&TestCase> {
+        self.tests
+            .iter()
+            .filter(|test| test.status == TestStatus::Failed)
+            .collect()
+    }
+
+    /// Attaches a `---- name stdout ----` section to its test
+    fn close_section(&mut self, section: Option<(String, Vec<&str>)>) {
+        let (name, lines) = match section {
+            Some(section) => section,
+            _ => return,
+        };
+        let stdout = lines.join("\n").trim_end().to_owned();
+        if let Some(test) = self.tests.iter_mut().find(|test| test.name == name)
+        {
+            test.panic_message =
+                Panic::parse(&stdout).map(|panic| panic.message);
+            test.stdout = stdout;
+        }
+    }
+
+    /// Adds the totals of a `test result:` line
+    fn add_totals(&mut self, summary: &str) {
+        for part in summary.split(|c| c == '.' || c == ';') {
+            let mut words = part.split_whitespace();
+            let count = match words.next().and_then(|n| n.parse::<usize>().ok())
+            {
+                Some(count) => count,
+                _ => continue,
+            };
+            match words.next() {
+                Some("passed") => self.passed += count,
+                Some("failed") => self.failed += count,
+                Some("ignored") => self.ignored += count,
+                Some("measured") => self.measured += count,
+                Some("filtered") => self.filtered += count,
+                _ => (),
+            }
+        }
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "test result: {}. {} passed; {} failed; {} ignored; {} measured; \
+             {} filtered out",
+            if self.success() { "ok" } else { "FAILED" },
+            self.passed,
+            self.failed,
+            self.ignored,
+            self.measured,
+            self.filtered
+        )
+    }
+}
+
+/// Returns the test name of a `---- name stdout ----` header
+fn section_header(line: &str) -> Option<&str> {
+    if line.starts_with("---- ") && line.ends_with(" stdout ----") {
+        Some(&line[5..line.len() - 12])
+    } else {
+        None
+    }
+}
+
+/// Parses a `test name ... status` line
+fn test_line(line: &str) -> Option<TestCase> {
+    if !line.starts_with("test ") {
+        return None;
+    }
+    let idx = line.find(" ... ")?;
+    let status = match &line[idx + 5..] {
+        "ok" => TestStatus::Passed,
+        "FAILED" => TestStatus::Failed,
+        status if status.starts_with("ignored") => TestStatus::Ignored,
+        status if status.starts_with("bench:") => TestStatus::Measured,
+        _ => return None,
+    };
+    Some(TestCase {
+        name: line[5..idx].to_owned(),
+        status,
+        stdout: String::new(),
+        panic_message: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "
+running 3 tests
+test tests::a ... ok
+test tests::b ... FAILED
+test tests::c ... ignored
+
+failures:
+
+---- tests::b stdout ----
+checking b
+thread 'tests::b' panicked at 'assertion failed: false', src/lib.rs:10:5
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    tests::b
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 2 filtered \
+out; finished in 0.00s
+
+";
+
+    #[test]
+    fn parses_test_output() {
+        let report = TestReport::parse(OUTPUT).unwrap();
+        assert_eq!(
+            (
+                report.passed,
+                report.failed,
+                report.ignored,
+                report.measured,
+                report.filtered
+            ),
+            (1, 1, 1, 0, 2)
+        );
+        assert_eq!(report.tests.len(), 3);
+        assert_eq!(report.tests[2].status, TestStatus::Ignored);
+        let failures = report.failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "tests::b");
+        assert_eq!(
+            failures[0].panic_message,
+            Some("assertion failed: false".into())
+        );
+        assert!(failures[0].stdout.starts_with("checking b\n"));
+        assert_eq!(
+            report.to_string(),
+            "test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; \
+             2 filtered out"
+        );
+    }
+
+    #[test]
+    fn requires_a_summary() {
+        assert_eq!(TestReport::parse("hello world"), None);
+    }
+}
@@ -0,0 +1,252 @@
+//! Client-side request throttling
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Loop};
+use futures::task::{self, Task};
+use futures::{Async, Future as StdFuture, Poll};
+use tokio_timer::{self, Timer};
+
+use {Error, Future};
+
+/// Rate and concurrency limits shared by all clones of a `Client`
+#[derive(Clone, Default)]
+pub(crate) struct Throttle {
+    rate: Option<RateLimit>,
+    concurrency: Option<Semaphore>,
+}
+
+impl Throttle {
+    pub fn new(
+        rate: Option<RateLimit>,
+        concurrency: Option<Semaphore>,
+    ) -> Self {
+        Throttle { rate, concurrency }
+    }
+
+    /// Resolves once a request may be sent
+    ///
+    /// The returned permit, if any, should be held until the request completes
+    pub fn acquire(&self) -> Future<Option<Permit>> {
+        let permit: Future<Option<Permit>> = match self.concurrency {
+            Some(ref semaphore) => Box::new(semaphore.acquire().map(Some)),
+            _ => Box::new(future::ok(None)),
+        };
+        match self.rate.clone() {
+            Some(rate) => {
+                Box::new(permit.and_then(move |permit| {
+                    rate.acquire().map(move |_| permit)
+                }))
+            }
+            _ => permit,
+        }
+    }
+}
+
+/// A token bucket allowing bursts of up to `capacity` requests
+#[derive(Clone)]
+pub(crate) struct RateLimit {
+    bucket: Arc<Mutex<Bucket>>,
+    timer: Timer,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, per: Duration) -> Self {
+        RateLimit {
+            bucket: Arc::new(Mutex::new(Bucket::new(
+                requests,
+                per,
+                Instant::now(),
+            ))),
+            // waits for a token last up to `per`
+            timer: tokio_timer::wheel()
+                .max_timeout(per)
+                .thread_name("recess-limit")
+                .build(),
+        }
+    }
+
+    /// Resolves once a token has been taken from the bucket
+    fn acquire(&self) -> Future<()> {
+        let rate = self.clone();
+        Box::new(future::loop_fn((), move |_| {
+            let taken = rate.bucket.lock().unwrap().take(Instant::now());
+            let waited: Future<Loop<(), ()>> = match taken {
+                Ok(()) => Box::new(future::ok(Loop::Break(()))),
+                Err(wait) => Box::new(
+                    rate.timer
+                        .sleep(wait)
+                        .map(|_| Loop::Continue(()))
+                        .map_err(Error::from),
+                ),
+            };
+            waited
+        }))
+    }
+}
+
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    /// tokens added per second
+    refill: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(requests: u32, per: Duration, now: Instant) -> Self {
+        let capacity = f64::from(requests);
+        Bucket {
+            capacity,
+            tokens: capacity,
+            refill: capacity / seconds(per).max(::std::f64::EPSILON),
+            refilled: now,
+        }
+    }
+
+    /// Takes a token, or returns how long to wait until one is available
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        if now > self.refilled {
+            let elapsed = seconds(now - self.refilled);
+            self.tokens =
+                (self.tokens + elapsed * self.refill).min(self.capacity);
+            self.refilled = now;
+        }
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / self.refill;
+            Err(Duration::from_millis((wait * 1000.0).ceil() as u64))
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+/// Limits the number of requests in flight
+#[derive(Clone)]
+pub(crate) struct Semaphore {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    permits: usize,
+    waiting: Vec<Task>,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            state: Arc::new(Mutex::new(State {
+                permits,
+                waiting: Vec::new(),
+            })),
+        }
+    }
+
+    fn acquire(&self) -> Acquire {
+        Acquire {
+            semaphore: self.clone(),
+        }
+    }
+
+    fn try_acquire(&self) -> Option<Permit> {
+        let mut state = self.state.lock().unwrap();
+        if state.permits > 0 {
+            state.permits -= 1;
+            Some(Permit {
+                semaphore: self.clone(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Resolves to a `Permit` once one is available
+struct Acquire {
+    semaphore: Semaphore,
+}
+
+impl StdFuture for Acquire {
+    type Item = Permit;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Permit, Error> {
+        if let Some(permit) = self.semaphore.try_acquire() {
+            return Ok(Async::Ready(permit));
+        }
+        let mut state = self.semaphore.state.lock().unwrap();
+        // a permit may have been released while we weren't holding the lock
+        if state.permits > 0 {
+            task::current().notify();
+        } else {
+            state.waiting.push(task::current());
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+/// A slot for a request in flight, released when dropped
+pub(crate) struct Permit {
+    semaphore: Semaphore,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let waiting = {
+            let mut state = self.semaphore.state.lock().unwrap();
+            state.permits += 1;
+            state.waiting.drain(..).collect::<Vec<_>>()
+        };
+        for task in waiting {
+            task.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_bursts_up_to_capacity() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(2, Duration::from_secs(1), now);
+        assert_eq!(bucket.take(now), Ok(()));
+        assert_eq!(bucket.take(now), Ok(()));
+        assert_eq!(bucket.take(now), Err(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(1, Duration::from_secs(2), now);
+        assert_eq!(bucket.take(now), Ok(()));
+        assert_eq!(
+            bucket.take(now + Duration::from_secs(1)),
+            Err(Duration::from_secs(1))
+        );
+        assert_eq!(bucket.take(now + Duration::from_secs(2)), Ok(()));
+    }
+
+    #[test]
+    fn semaphore_releases_permits_on_drop() {
+        let semaphore = Semaphore::new(1);
+        let permit = semaphore.acquire().wait().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+        drop(permit);
+        assert!(semaphore.try_acquire().is_some());
+    }
+
+    #[test]
+    fn semaphore_is_shared_across_clones() {
+        let semaphore = Semaphore::new(1);
+        let _permit = semaphore.clone().try_acquire().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+    }
+}
@@ -4,7 +4,8 @@
 #![allow(unused_mut)]
 
 /// Parameters for compiling rustlang code
-#[derive(Debug, Serialize, Default, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct Request {
     code: String,
 }
@@ -20,7 +21,7 @@ impl Request {
 }
 
 /// Lint operation response
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
     /// Indicates if request was successful or not
     pub success: bool,
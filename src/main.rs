@@ -1,30 +1,91 @@
 extern crate clap;
 extern crate futures;
+extern crate notify;
 extern crate recess;
+extern crate rustyline;
+extern crate serde;
+extern crate serde_json;
 extern crate tokio;
 #[macro_use]
 extern crate structopt;
 
-use std::io::stdin;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, stdin, stdout, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc;
+use std::time::Duration;
 
-use futures::Future;
-use recess::compile::Target;
+use futures::sync::oneshot;
+use futures::{future, stream, Future, Stream};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use recess::asm;
+use recess::batch::{self, Report, Summary};
+use recess::case::{self, Case};
+use recess::compare::{self, Variant};
+use recess::compile::{HideAssemblerDirectives, Target};
+use recess::config::{Config, OutputFormat, Profile};
+use recess::diff;
+use recess::directive::Directives;
+use recess::doctest::{self, Crate};
+use recess::eval::Transport;
+use recess::ir;
+use recess::libtest::TestReport;
+use recess::markdown;
+use recess::repl::{self, Command, Session};
+use recess::snippet::{self, Status};
 use recess::{
-    Channel, Client, CompileRequest, CrateType, ExecuteRequest, FormatRequest,
+    AsmFlavor, Backtrace, Channel, Client, CompileRequest, CrateType, Edition,
+    EvalRequest, ExecuteRequest, FormatRequest, LintRequest, Mode, OptLevel,
+    Project,
 };
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+use serde::Serialize;
 use structopt::StructOpt;
 use tokio::runtime::Runtime;
 
 /// CLI options
 #[derive(StructOpt, PartialEq, Debug)]
 #[structopt(name = "recess", about = "Rust playground cli")]
+struct Cli {
+    /// Profile of recess.toml settings to use, `RECESS_PROFILE` by default
+    #[structopt(long = "profile", raw(global = "true"))]
+    profile: Option<String>,
+    /// How to print the responses of execute, compile, eval, format, clippy
+    /// and run
+    #[structopt(
+        long = "format",
+        raw(global = "true", possible_values = "&OutputFormat::variants()")
+    )]
+    format: Option<OutputFormat>,
+    #[structopt(subcommand)]
+    command: Options,
+}
+
+/// CLI subcommands
+#[derive(StructOpt, PartialEq, Debug)]
 enum Options {
     #[structopt(
         name = "execute", alias = "exec", about = "Execute source code"
     )]
     Execute {
-        #[structopt(short = "s", long = "src")]
-        code: String,
+        #[structopt(short = "s", long = "src", required_unless = "path")]
+        code: Option<String>,
+        /// A .rs file or a project directory to execute, sent as is to
+        /// self-hosted playgrounds and with its `mod` files inlined for the
+        /// public one
+        #[structopt(parse(from_os_str), conflicts_with = "code")]
+        path: Option<PathBuf>,
+        /// Run again whenever the source file, or a file of the project
+        /// directory, changes
+        #[structopt(long = "watch", requires = "path")]
+        watch: bool,
+        /// Run the code's tests and print a summary of their results
+        #[structopt(long = "tests")]
+        tests: bool,
         #[structopt(
             short = "c",
             long = "channel",
@@ -35,11 +96,40 @@ enum Options {
             long = "crate_type", raw(possible_values = "&CrateType::variants()")
         )]
         crate_type: Option<CrateType>,
+        #[structopt(
+            long = "opt-level", raw(possible_values = "&OptLevel::variants()")
+        )]
+        opt_level: Option<OptLevel>,
+        #[structopt(
+            long = "backtrace", raw(possible_values = "&Backtrace::variants()")
+        )]
+        backtrace: Option<Backtrace>,
+        /// Configuration flags to pass to rustc
+        #[structopt(long = "cfg", number_of_values = 1)]
+        cfgs: Vec<String>,
+        /// Codegen options to pass to rustc, as in `-C target-cpu=native`
+        #[structopt(short = "C", number_of_values = 1)]
+        codegen: Vec<String>,
+        /// Environment variables, as in `--env RUST_LOG=debug`
+        #[structopt(
+            long = "env",
+            number_of_values = 1,
+            parse(try_from_str = "parse_env")
+        )]
+        env: Vec<(String, String)>,
     },
     #[structopt(name = "compile", about = "Compile source code")]
     Compile {
-        #[structopt(short = "s", long = "src")]
-        code: String,
+        #[structopt(short = "s", long = "src", required_unless = "path")]
+        code: Option<String>,
+        /// A .rs file or a project directory to compile, with its `mod`
+        /// files inlined
+        #[structopt(parse(from_os_str), conflicts_with = "code")]
+        path: Option<PathBuf>,
+        /// Run again whenever the source file, or a file of the project
+        /// directory, changes
+        #[structopt(long = "watch", requires = "path")]
+        watch: bool,
         #[structopt(
             short = "t",
             long = "target",
@@ -56,11 +146,218 @@ enum Options {
             long = "crate_type", raw(possible_values = "&CrateType::variants()")
         )]
         crate_type: Option<CrateType>,
+        #[structopt(
+            long = "opt-level", raw(possible_values = "&OptLevel::variants()")
+        )]
+        opt_level: Option<OptLevel>,
+        #[structopt(
+            long = "backtrace", raw(possible_values = "&Backtrace::variants()")
+        )]
+        backtrace: Option<Backtrace>,
+        /// Configuration flags to pass to rustc
+        #[structopt(long = "cfg", number_of_values = 1)]
+        cfgs: Vec<String>,
+        /// Codegen options to pass to rustc, as in `-C target-cpu=native`
+        #[structopt(short = "C", number_of_values = 1)]
+        codegen: Vec<String>,
+        /// Environment variables, as in `--env RUST_LOG=debug`
+        #[structopt(
+            long = "env",
+            number_of_values = 1,
+            parse(try_from_str = "parse_env")
+        )]
+        env: Vec<(String, String)>,
+        /// Only print the output for functions with this name
+        #[structopt(short = "f", long = "function")]
+        function: Option<String>,
+        /// Keep assembler directives, such as `.loc` source lines
+        #[structopt(long = "directives")]
+        directives: bool,
+        /// Remove debug metadata from llvm-ir and mir output
+        #[structopt(long = "strip-debug")]
+        strip_debug: bool,
+        /// Write the compiled wasm module to a file
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Write wasm modules in the text format rather than binary
+        #[structopt(long = "wat")]
+        wat: bool,
+    },
+    #[structopt(
+        name = "eval", about = "Evaluate an expression and print its value"
+    )]
+    Eval {
+        /// An expression, or statements ending in one, `-` to read stdin
+        expression: String,
+        #[structopt(
+            short = "c",
+            long = "channel",
+            raw(possible_values = "&Channel::variants()")
+        )]
+        channel: Option<Channel>,
+        #[structopt(long = "mode", raw(possible_values = "&Mode::variants()"))]
+        mode: Option<Mode>,
+        #[structopt(
+            long = "edition", raw(possible_values = "&Edition::variants()")
+        )]
+        edition: Option<Edition>,
+        #[structopt(
+            long = "backtrace", raw(possible_values = "&Backtrace::variants()")
+        )]
+        backtrace: Option<Backtrace>,
+        /// Endpoint to evaluate with, `evaluate` for the legacy
+        /// `/evaluate.json`
+        #[structopt(
+            long = "transport", raw(possible_values = "&Transport::variants()")
+        )]
+        transport: Option<Transport>,
+    },
+    #[structopt(name = "repl", about = "Evaluate source code interactively")]
+    Repl {
+        #[structopt(
+            short = "c",
+            long = "channel",
+            raw(possible_values = "&Channel::variants()")
+        )]
+        channel: Option<Channel>,
+        #[structopt(long = "mode", raw(possible_values = "&Mode::variants()"))]
+        mode: Option<Mode>,
+        #[structopt(
+            long = "edition", raw(possible_values = "&Edition::variants()")
+        )]
+        edition: Option<Edition>,
     },
     #[structopt(name = "format", alias = "fmt", about = "Format source code")]
     Format {
+        #[structopt(short = "s", long = "src", required_unless = "path")]
+        code: Option<String>,
+        /// A .rs file to format
+        #[structopt(parse(from_os_str), conflicts_with = "code")]
+        path: Option<PathBuf>,
+        /// Run again whenever the source file changes
+        #[structopt(long = "watch", requires = "path")]
+        watch: bool,
+    },
+    #[structopt(name = "clippy", about = "Lint source code with clippy")]
+    Clippy {
+        #[structopt(short = "s", long = "src", required_unless = "path")]
+        code: Option<String>,
+        /// A .rs file or a project directory to lint, with its `mod` files
+        /// inlined
+        #[structopt(parse(from_os_str), conflicts_with = "code")]
+        path: Option<PathBuf>,
+        /// Run again whenever the source file, or a file of the project
+        /// directory, changes
+        #[structopt(long = "watch", requires = "path")]
+        watch: bool,
+    },
+    #[structopt(
+        name = "run",
+        about = "Send the request of a .toml, .json or .yaml request file"
+    )]
+    Run {
+        /// A request file, naming its endpoint and holding its code inline
+        /// or by path
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    #[structopt(
+        name = "compare",
+        about = "Compare source code behavior across release channels"
+    )]
+    Compare {
         #[structopt(short = "s", long = "src")]
         code: String,
+        /// Compile rather than execute the source code
+        #[structopt(long = "compile")]
+        compile: bool,
+        /// Also compare debug and release modes
+        #[structopt(long = "modes")]
+        modes: bool,
+        #[structopt(
+            long = "crate_type", raw(possible_values = "&CrateType::variants()")
+        )]
+        crate_type: Option<CrateType>,
+        /// Total width of the side by side columns
+        #[structopt(short = "w", long = "width", default_value = "100")]
+        width: usize,
+    },
+    #[structopt(
+        name = "asm-diff",
+        about = "Diff the assembly generated for two versions of source code"
+    )]
+    AsmDiff {
+        #[structopt(short = "l", long = "left")]
+        left: String,
+        /// Source code of the right hand side, the left's by default
+        #[structopt(short = "r", long = "right")]
+        right: Option<String>,
+        #[structopt(
+            short = "c",
+            long = "channel",
+            raw(possible_values = "&Channel::variants()")
+        )]
+        channel: Option<Channel>,
+        #[structopt(
+            long = "left-mode", raw(possible_values = "&Mode::variants()")
+        )]
+        left_mode: Option<Mode>,
+        #[structopt(
+            long = "right-mode", raw(possible_values = "&Mode::variants()")
+        )]
+        right_mode: Option<Mode>,
+        #[structopt(
+            long = "left-flavor",
+            raw(possible_values = "&AsmFlavor::variants()")
+        )]
+        left_flavor: Option<AsmFlavor>,
+        #[structopt(
+            long = "right-flavor",
+            raw(possible_values = "&AsmFlavor::variants()")
+        )]
+        right_flavor: Option<AsmFlavor>,
+        #[structopt(
+            long = "crate_type", raw(possible_values = "&CrateType::variants()")
+        )]
+        crate_type: Option<CrateType>,
+        /// Lines of context around each change
+        #[structopt(short = "U", long = "context", default_value = "3")]
+        context: usize,
+    },
+    #[structopt(
+        name = "batch",
+        about = "Execute a directory of .rs files or a file of ndjson requests"
+    )]
+    Batch {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        #[structopt(short = "j", long = "concurrency", default_value = "4")]
+        concurrency: usize,
+    },
+    #[structopt(
+        name = "check-md", about = "Check rust code blocks in Markdown files"
+    )]
+    CheckMd {
+        #[structopt(parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+        #[structopt(short = "j", long = "concurrency", default_value = "4")]
+        concurrency: usize,
+    },
+    #[structopt(
+        name = "doctest", about = "Run the doc examples of a local crate"
+    )]
+    Doctest {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        path: PathBuf,
+        /// Channels to run examples on, all of them by default
+        #[structopt(
+            short = "c",
+            long = "channel",
+            raw(possible_values = "&Channel::variants()")
+        )]
+        channels: Vec<Channel>,
+        #[structopt(short = "j", long = "concurrency", default_value = "4")]
+        concurrency: usize,
     },
 }
 
@@ -73,61 +370,649 @@ fn src(code: String) -> String {
     buffer
 }
 
+/// Parses a `KEY=VALUE` environment variable
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    match s.find('=') {
+        Some(idx) if idx > 0 => Ok((s[..idx].into(), s[idx + 1..].into())),
+        _ => Err(format!("expected KEY=VALUE, found {}", s)),
+    }
+}
+
+/// Returns rustc arguments for a list of `-C` codegen options
+fn rustc_args(codegen: Vec<String>) -> Vec<String> {
+    codegen
+        .into_iter()
+        .flat_map(|option| vec!["-C".to_string(), option])
+        .collect()
+}
+
+/// Source code given inline or read from a file, or a project read from a
+/// directory
+enum Source {
+    Code(String),
+    Project(Project),
+}
+
+impl Source {
+    /// Reads source code from a file or a project from a directory
+    fn open(path: &PathBuf) -> Result<Source, recess::Error> {
+        if path.is_dir() {
+            Ok(Source::Project(Project::open(path)?))
+        } else {
+            read(path).map(Source::Code)
+        }
+    }
+
+    /// Returns the code of the file which carries directives, a project's
+    /// crate root
+    fn root(&self) -> &str {
+        match *self {
+            Source::Code(ref code) => code,
+            Source::Project(ref project) => project
+                .root()
+                .map_or("", |root| project.files[root].as_str()),
+        }
+    }
+
+    /// Returns the source code, bundling a project into a single file
+    fn code(self) -> Result<String, recess::Error> {
+        match self {
+            Source::Code(code) => Ok(code),
+            Source::Project(project) => project.bundle(),
+        }
+    }
+}
+
+/// Returns the options of source code: the profile's, overridden by the
+/// source's directives, themselves overridden by flags
+fn options_of(
+    defaults: &Directives,
+    code: &str,
+    flags: &Directives,
+) -> Result<Directives, recess::Error> {
+    let mut options = defaults.clone();
+    options.merge(&Directives::parse(code)?);
+    options.merge(flags);
+    Ok(options)
+}
+
+/// Adapts a subcommand on source code to run on projects bundled into a
+/// single file
+fn bundled<F>(run: F) -> impl Fn(Source) -> recess::Future<bool>
+where
+    F: Fn(String) -> recess::Future<bool>,
+{
+    move |source: Source| match source.code() {
+        Ok(code) => run(code),
+        Err(err) => Box::new(future::err(err)),
+    }
+}
+
+fn read(path: &PathBuf) -> Result<String, recess::Error> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Selects a function's output from compiled code, returning `None` if
+/// there is no such function, and strips llvm-ir and mir debug metadata if
+/// requested
+fn select(
+    target: &Target,
+    code: String,
+    function: &Option<String>,
+    strip_debug: bool,
+) -> Option<String> {
+    let sections = match (target, function.as_ref()) {
+        // compile doesn't set a flavor, so its output is AT&T's
+        (&Target::Asm, Some(function)) => asm::symbols(&code, &AsmFlavor::Att)
+            .into_iter()
+            .filter(|symbol| symbol.matches(function))
+            .map(|symbol| symbol.code)
+            .collect::<Vec<_>>(),
+        (&Target::Llvm, Some(function)) | (&Target::Mir, Some(function)) => {
+            let module = if *target == Target::Llvm {
+                ir::Module::llvm(&code)
+            } else {
+                ir::Module::mir(&code)
+            };
+            module
+                .functions
+                .into_iter()
+                .filter(|f| f.matches(function))
+                .map(|f| f.code)
+                .collect::<Vec<_>>()
+        }
+        _ => vec![code],
+    };
+    if sections.is_empty() {
+        return None;
+    }
+    let code = sections.join("\n");
+    Some(match *target {
+        Target::Llvm | Target::Mir if strip_debug => ir::strip_debug(&code),
+        _ => code,
+    })
+}
+
+/// Runs a subcommand on source code, exiting if it fails, or, with
+/// `--watch`, on every change of its source until interrupted
+fn drive<F>(
+    runtime: &mut Runtime,
+    code: Option<String>,
+    path: Option<PathBuf>,
+    watching: bool,
+    run: F,
+) -> Result<(), recess::Error>
+where
+    F: Fn(Source) -> recess::Future<bool>,
+{
+    if let (true, Some(path)) = (watching, path.as_ref()) {
+        return watch(runtime, path, run);
+    }
+    let source = match (code, path) {
+        (Some(code), _) => Source::Code(src(code)),
+        (_, Some(path)) => match Source::open(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1)
+            }
+        },
+        _ => unreachable!("structopt requires code or path"),
+    };
+    if !runtime.block_on(run(source))? {
+        exit(1)
+    }
+    Ok(())
+}
+
+/// Clears the screen and runs a subcommand on a file or project directory
+/// each time it changes
+///
+/// Changes are debounced and a run still in flight when the source changes
+/// again is cancelled.
+fn watch<F>(
+    runtime: &mut Runtime,
+    path: &PathBuf,
+    run: F,
+) -> Result<(), recess::Error>
+where
+    F: Fn(Source) -> recess::Future<bool>,
+{
+    let path = path.canonicalize()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(200))
+        .map_err(watch_error)?;
+    // editors often save by replacing files, so files are watched through
+    // their directory
+    let (dir, mode) = if path.is_dir() {
+        (path.as_path(), RecursiveMode::Recursive)
+    } else {
+        (
+            path.parent().unwrap_or_else(|| Path::new("/")),
+            RecursiveMode::NonRecursive,
+        )
+    };
+    watcher.watch(dir, mode).map_err(watch_error)?;
+    loop {
+        print!("\x1b[2J\x1b[H");
+        println!("[watching {}, ctrl-c to stop]", path.display());
+        stdout().flush()?;
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        match Source::open(&path) {
+            Ok(source) => {
+                runtime.spawn(
+                    run(source)
+                        .map(|_| ())
+                        .map_err(|err| eprintln!("{}", err))
+                        .select(cancelled.map_err(|_| ()))
+                        .then(|_| Ok(())),
+                );
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+        loop {
+            let changed = match rx.recv() {
+                Ok(DebouncedEvent::Create(ref changed))
+                | Ok(DebouncedEvent::Write(ref changed))
+                | Ok(DebouncedEvent::Remove(ref changed))
+                | Ok(DebouncedEvent::Rename(_, ref changed)) => {
+                    changed.clone()
+                }
+                Ok(_) => continue,
+                // the watcher is gone
+                Err(_) => return Ok(()),
+            };
+            if changed.starts_with(&path) {
+                break;
+            }
+        }
+        // cancels the run if it's still in flight
+        let _ = cancel.send(());
+    }
+}
+
+fn watch_error(err: notify::Error) -> recess::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string()).into()
+}
+
+/// Runs an interactive session, keeping line history in `~/.recess_history`
+fn repl(runtime: &mut Runtime, client: &Client, mut session: Session) {
+    let history = env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".recess_history"));
+    let mut editor = Editor::<()>::new();
+    if let Some(ref history) = history {
+        // there's no history before the first session
+        let _ = editor.load_history(history);
+    }
+    println!("recess repl, :help for commands");
+    let mut entry = String::new();
+    loop {
+        let prompt = if entry.is_empty() { ">> " } else { ".. " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                entry.push_str(&line);
+                entry.push('\n');
+            }
+            Err(ReadlineError::Interrupted) => {
+                entry.clear();
+                continue;
+            }
+            Err(_) => break,
+        }
+        if !repl::is_complete(&entry) {
+            continue;
+        }
+        let input = entry.trim().to_owned();
+        entry.clear();
+        if input.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(input.as_str());
+        if !input.starts_with(':') {
+            match runtime.block_on(client.execute(session.request(&input))) {
+                Ok(response) => {
+                    let (stdout, stderr) = repl::newest_output(&response);
+                    print!("{}", stdout);
+                    eprint!("{}", stderr);
+                    if response.success {
+                        session.push(&input);
+                    }
+                }
+                Err(err) => eprintln!("{}", err),
+            }
+            continue;
+        }
+        match input.parse::<Command>() {
+            Ok(Command::Channel(Some(channel))) => {
+                session.channel = Some(channel)
+            }
+            Ok(Command::Channel(None)) => {
+                println!("{}", or_default(&session.channel))
+            }
+            Ok(Command::Mode(Some(mode))) => session.mode = Some(mode),
+            Ok(Command::Mode(None)) => {
+                println!("{}", or_default(&session.mode))
+            }
+            Ok(Command::Edition(Some(edition))) => {
+                session.edition = Some(edition)
+            }
+            Ok(Command::Edition(None)) => {
+                println!("{}", or_default(&session.edition))
+            }
+            Ok(Command::Reset) => session.reset(),
+            Ok(Command::Show) => print!("{}", session.program(None)),
+            Ok(Command::Asm) => {
+                match runtime.block_on(client.compile(session.asm_request())) {
+                    Ok(ref response) if response.success => {
+                        println!("{}", response.code)
+                    }
+                    Ok(response) => eprint!("{}", response.stderr),
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            Ok(Command::Help) => println!("{}", Command::help()),
+            Ok(Command::Quit) => break,
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    if let Some(ref history) = history {
+        if let Err(err) = editor.save_history(history) {
+            eprintln!("failed to save history: {}", err);
+        }
+    }
+}
+
+/// Describes a session option, which is the client's default when unset
+fn or_default<T: std::fmt::Display>(option: &Option<T>) -> String {
+    option
+        .as_ref()
+        .map_or_else(|| "default".to_string(), ToString::to_string)
+}
+
+/// Loads the settings of a `recess.toml` profile, overridden by `RECESS_*`
+/// environment variables
+fn settings(profile: Option<String>) -> Result<Profile, recess::Error> {
+    let name = profile.or_else(|| env::var("RECESS_PROFILE").ok());
+    let mut profile =
+        Config::load()?.profile(name.as_ref().map(String::as_str))?;
+    profile.merge(&Profile::from_env()?);
+    Ok(profile)
+}
+
+/// Prints a response as a JSON document
+fn render_json<T: Serialize>(response: &T) {
+    match serde_json::to_string_pretty(response) {
+        Ok(json) => println!("{}", json),
+        Err(err) => eprintln!("{}", err),
+    }
+}
+
+/// Prints the output of a request file's response
+fn render_case(response: &case::Response) {
+    let (code, stdout, stderr) = match *response {
+        case::Response::Execute(ref result) => {
+            (None, &result.stdout, &result.stderr)
+        }
+        case::Response::Compile(ref result) => {
+            (Some(&result.code), &result.stdout, &result.stderr)
+        }
+        case::Response::Format(ref result) => {
+            (Some(&result.code), &result.stdout, &result.stderr)
+        }
+        case::Response::Lint(ref result) => {
+            (None, &result.stdout, &result.stderr)
+        }
+    };
+    for line in code.into_iter().flat_map(|code| code.lines()) {
+        println!("{}", line);
+    }
+    for line in stdout.lines() {
+        println!("{}", line);
+    }
+    for line in stderr.lines() {
+        eprintln!("{}", line);
+    }
+}
+
+/// Prints a compact summary of a test run: its failures, with their panic
+/// messages, and totals
+fn render_tests(report: &TestReport) {
+    for test in report.failures() {
+        match test.panic_message {
+            Some(ref message) => println!("FAILED {}: {}", test.name, message),
+            _ => println!("FAILED {}", test.name),
+        }
+    }
+    println!("{}", report);
+}
+
+/// Prints a side by side comparison of each field of a set of variants
+fn render_comparison(variants: &[Variant], width: usize) {
+    let fields: [(&str, fn(&Variant) -> String); 3] = [
+        ("success", |variant| match variant.error {
+            Some(ref err) => format!("error: {}", err),
+            _ => variant.success.to_string(),
+        }),
+        ("stdout", |variant| variant.stdout.clone()),
+        ("stderr", |variant| variant.stderr.clone()),
+    ];
+    for &(name, field) in &fields {
+        println!("== {} ==", name);
+        let columns = variants
+            .iter()
+            .map(|variant| (variant.label(), field(variant)))
+            .collect::<Vec<_>>();
+        println!("{}", diff::side_by_side(&columns, width));
+    }
+    if compare::differs(variants) {
+        println!("compare result: variants differ");
+    } else {
+        println!("compare result: all variants agree");
+    }
+}
+
+/// Prints snippet check results in the style of `cargo test`, returning
+/// true if none failed
+fn render_checks(results: &[(String, Status)]) -> bool {
+    let (mut passed, mut failed, mut ignored) = (0, 0, 0);
+    for &(ref name, ref status) in results {
+        let label = match *status {
+            Status::Ok => {
+                passed += 1;
+                "ok"
+            }
+            Status::Ignored => {
+                ignored += 1;
+                "ignored"
+            }
+            Status::Failed { .. } => {
+                failed += 1;
+                "FAILED"
+            }
+        };
+        println!("test {} ... {}", name, label);
+    }
+    if failed > 0 {
+        println!("\nfailures:\n");
+        for &(ref name, ref status) in results {
+            if let Status::Failed {
+                ref reason,
+                ref stdout,
+                ref stderr,
+            } = *status
+            {
+                println!("---- {} ----", name);
+                println!("{}", reason);
+                for line in stdout.lines().chain(stderr.lines()) {
+                    println!("{}", line);
+                }
+                println!();
+            }
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} ignored",
+        if failed == 0 { "ok" } else { "FAILED" },
+        passed,
+        failed,
+        ignored
+    );
+    failed == 0
+}
+
 fn main() {
     let mut runtime = Runtime::new().expect("failed to initialize runtime");
-    let result = match Options::from_args() {
+    let cli = Cli::from_args();
+    let profile = match settings(cli.profile) {
+        Ok(profile) => profile,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1)
+        }
+    };
+    let client = match profile.client() {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1)
+        }
+    };
+    let json = cli.format.or(profile.format).unwrap_or_default()
+        == OutputFormat::Json;
+    let defaults = Directives {
+        crate_type: profile.crate_type,
+        ..Default::default()
+    };
+    let result = match cli.command {
         Options::Execute {
             code,
+            path,
+            watch,
+            tests,
             channel,
             crate_type,
+            opt_level,
+            backtrace,
+            cfgs,
+            codegen,
+            env,
         } => {
-            let mut options = ExecuteRequest::builder(src(code));
-
-            for c in channel {
-                options.channel(c);
-            }
-            for t in crate_type {
-                options.crate_type(t);
-            }
+            let flags = Directives {
+                channel,
+                crate_type,
+                opt_level,
+                backtrace,
+                tests: Some(true).filter(|_| tests),
+                ..Default::default()
+            };
+            let mut options = ExecuteRequest::builder(String::new());
+            options
+                .cfgs(cfgs)
+                .rustc_args(rustc_args(codegen))
+                .env(env.into_iter().collect::<BTreeMap<_, _>>());
 
-            let response = Client::new()
-                .execute(options.build().unwrap())
-                .and_then(|result| {
+            let run = move |source: Source| -> recess::Future<bool> {
+                let options_of_source =
+                    options_of(&defaults, source.root(), &flags);
+                let directives = match options_of_source {
+                    Ok(directives) => directives,
+                    Err(err) => return Box::new(future::err(err)),
+                };
+                let tests = directives.tests.unwrap_or_default();
+                let mut options = options.clone();
+                directives.configure_execute(&mut options);
+                let response = match source {
+                    Source::Code(code) => {
+                        client.execute(options.code(code).build().unwrap())
+                    }
+                    // self-hosted playgrounds take the project's files and
+                    // dependencies as they are
+                    Source::Project(project) => client
+                        .execute_project(&project, options.build().unwrap()),
+                };
+                Box::new(response.map(move |result| {
+                    let report = result.test_report().filter(|_| tests);
+                    if json {
+                        render_json(&result);
+                        return report
+                            .map_or(result.success, |report| report.success());
+                    }
+                    if let Some(report) = report {
+                        render_tests(&report);
+                        return report.success();
+                    }
                     for line in result.stdout.lines() {
                         println!("{}", line);
                     }
                     for line in result.stderr.lines() {
                         eprintln!("{}", line);
                     }
-                    Ok(())
-                });
-
-            runtime
-                .block_on(response)
-                .map_err(recess::Error::from)
-                .map(|_| ())
+                    result.success
+                }))
+            };
+            drive(&mut runtime, code, path, watch, run)
         }
         Options::Compile {
             code,
+            path,
+            watch,
             target,
             channel,
             crate_type,
+            opt_level,
+            backtrace,
+            cfgs,
+            codegen,
+            env,
+            function,
+            directives,
+            strip_debug,
+            output,
+            wat,
         } => {
-            let mut options = CompileRequest::builder(src(code));
+            let mut options = CompileRequest::builder(String::new());
+            if target == Some(Target::Wasm)
+                && (function.is_some() || strip_debug)
+            {
+                eprintln!("--function and --strip-debug don't support wasm");
+                exit(1)
+            }
+            let kind = target.clone().unwrap_or_default();
+            if (output.is_some() || wat) && kind != Target::Wasm {
+                eprintln!("--output and --wat are only supported for wasm");
+                exit(1)
+            }
             for t in target {
                 options.target(t);
             }
-            for c in channel {
-                options.channel(c);
-            }
-            for t in crate_type {
-                options.crate_type(t);
+            let flags = Directives {
+                channel,
+                crate_type,
+                opt_level,
+                backtrace,
+                ..Default::default()
+            };
+            options
+                .cfgs(cfgs)
+                .rustc_args(rustc_args(codegen))
+                .env(env.into_iter().collect::<BTreeMap<_, _>>());
+            if directives {
+                options
+                    .hide_assembler_directives(HideAssemblerDirectives::Show);
             }
 
-            let response = Client::new()
-                .compile(options.build().unwrap())
-                .and_then(|result| {
-                    for line in result.code.lines() {
+            let run = move |code: String| -> recess::Future<bool> {
+                let options_of_code = options_of(&defaults, &code, &flags);
+                let mut directives = match options_of_code {
+                    Ok(directives) => directives,
+                    Err(err) => return Box::new(future::err(err)),
+                };
+                // wasm is only available on nightly
+                if kind == Target::Wasm && directives.channel.is_none() {
+                    directives.channel = Some(Channel::Nightly);
+                }
+                let mut options = options.clone();
+                directives.configure_compile(&mut options);
+                let request = options.code(code).build().unwrap();
+                let (kind, output, function) =
+                    (kind.clone(), output.clone(), function.clone());
+                Box::new(client.compile(request).and_then(move |result| {
+                    if json {
+                        render_json(&result);
+                        return Ok(result.success);
+                    }
+                    let code = if !result.success {
+                        result.code
+                    } else if kind == Target::Wasm {
+                        let module = result.wasm()?;
+                        match output {
+                            Some(path) => {
+                                let bytes = if wat {
+                                    module.to_text()?.into_bytes()
+                                } else {
+                                    module.to_binary()?
+                                };
+                                File::create(path)?.write_all(&bytes)?;
+                                String::new()
+                            }
+                            _ => module.to_text()?,
+                        }
+                    } else {
+                        match select(&kind, result.code, &function, strip_debug)
+                        {
+                            Some(code) => code,
+                            _ => {
+                                eprintln!(
+                                    "no function named {}",
+                                    function.unwrap_or_default()
+                                );
+                                return Ok(false);
+                            }
+                        }
+                    };
+                    for line in code.lines() {
                         println!("{}", line);
                     }
                     for line in result.stdout.lines() {
@@ -136,37 +1021,328 @@ fn main() {
                     for line in result.stderr.lines() {
                         eprintln!("{}", line);
                     }
-                    Ok(())
-                });
+                    Ok(result.success)
+                }))
+            };
+            drive(&mut runtime, code, path, watch, bundled(run))
+        }
+        Options::Eval {
+            expression,
+            channel,
+            mode,
+            edition,
+            backtrace,
+            transport,
+        } => {
+            let mut options = EvalRequest::builder(src(expression));
+            options.channel(channel).mode(mode).edition(edition);
+            for b in backtrace {
+                options.backtrace(b);
+            }
+            for t in transport {
+                options.transport(t);
+            }
 
-            runtime
-                .block_on(response)
-                .map_err(recess::Error::from)
-                .map(|_| ())
-        }
-        Options::Format { code } => {
-            let response = Client::new()
-                .format(FormatRequest::new(src(code)))
-                .and_then(|result| {
-                    for line in result.code.lines() {
-                        println!("{}", line);
+            let response =
+                client.eval(options.build().unwrap()).map(move |result| {
+                    if json {
+                        render_json(&result);
+                        return result.success;
                     }
                     for line in result.stdout.lines() {
                         println!("{}", line);
                     }
-                    for line in result.stderr.lines() {
-                        eprintln!("{}", line);
+                    if !result.success {
+                        for line in result.stderr.lines() {
+                            eprintln!("{}", line);
+                        }
                     }
-                    Ok(())
+                    result.success
                 });
+            runtime.block_on(response).map(|success| {
+                if !success {
+                    exit(1)
+                }
+            })
+        }
+        Options::Repl {
+            channel,
+            mode,
+            edition,
+        } => {
+            let mut session = Session::new();
+            session.channel = channel;
+            session.mode = mode;
+            session.edition = edition;
+            repl(&mut runtime, &client, session);
+            Ok(())
+        }
+        Options::Format { code, path, watch } => {
+            // formatted projects come back bundled into one file, which
+            // can't be written back
+            if let Some(ref path) = path {
+                if path.is_dir() {
+                    eprintln!(
+                        "{} is a directory, not a .rs file",
+                        path.display()
+                    );
+                    exit(1)
+                }
+            }
+            let run = move |code: String| -> recess::Future<bool> {
+                Box::new(client.format(FormatRequest::new(code)).map(
+                    move |result| {
+                        if json {
+                            render_json(&result);
+                            return true;
+                        }
+                        for line in result.code.lines() {
+                            println!("{}", line);
+                        }
+                        for line in result.stdout.lines() {
+                            println!("{}", line);
+                        }
+                        for line in result.stderr.lines() {
+                            eprintln!("{}", line);
+                        }
+                        true
+                    },
+                ))
+            };
+            drive(&mut runtime, code, path, watch, bundled(run))
+        }
+        Options::Clippy { code, path, watch } => {
+            let run = move |code: String| -> recess::Future<bool> {
+                Box::new(client.lint(LintRequest::new(code)).map(
+                    move |result| {
+                        if json {
+                            render_json(&result);
+                            return result.success;
+                        }
+                        for line in result.stdout.lines() {
+                            println!("{}", line);
+                        }
+                        for line in result.stderr.lines() {
+                            eprintln!("{}", line);
+                        }
+                        result.success
+                    },
+                ))
+            };
+            drive(&mut runtime, code, path, watch, bundled(run))
+        }
+        Options::Run { path } => {
+            let response = Case::open(&path).map(|case| client.run(case));
+            response.and_then(|response| {
+                let result = runtime.block_on(response)?;
+                if json {
+                    render_json(&result);
+                } else {
+                    render_case(&result);
+                }
+                if !result.success() {
+                    exit(1)
+                }
+                Ok(())
+            })
+        }
+        Options::Compare {
+            code,
+            compile,
+            modes,
+            crate_type,
+            width,
+        } => {
+            let variants = if compile {
+                let mut options = CompileRequest::builder(src(code));
+                for t in crate_type {
+                    options.crate_type(t);
+                }
+                compare::compile(&client, &options.build().unwrap(), modes)
+            } else {
+                let mut options = ExecuteRequest::builder(src(code));
+                for t in crate_type {
+                    options.crate_type(t);
+                }
+                compare::execute(&client, &options.build().unwrap(), modes)
+            };
             runtime
-                .block_on(response)
-                .map_err(recess::Error::from)
-                .map(|_| ())
+                .block_on(variants)
+                .map(|variants| render_comparison(&variants, width))
+        }
+        Options::AsmDiff {
+            left,
+            right,
+            channel,
+            left_mode,
+            right_mode,
+            left_flavor,
+            right_flavor,
+            crate_type,
+            context,
+        } => {
+            let left = src(left);
+            let right = right.map(src).unwrap_or_else(|| left.clone());
+            let request = |code, mode, flavor| {
+                let mut options = CompileRequest::builder(code);
+                options.channel(channel.clone()).mode(mode);
+                for f in flavor {
+                    options.assembly_flavor(f);
+                }
+                for t in crate_type.clone() {
+                    options.crate_type(t);
+                }
+                options.build().unwrap()
+            };
+            let response = asm::diff(
+                &client,
+                request(left, left_mode, left_flavor),
+                request(right, right_mode, right_flavor),
+                context,
+            );
+            runtime.block_on(response).map(|diff| {
+                for &(side, ref response) in
+                    &[("left", &diff.left), ("right", &diff.right)]
+                {
+                    if !response.success {
+                        eprintln!("{} side failed to compile", side);
+                        for line in response.stderr.lines() {
+                            eprintln!("{}", line);
+                        }
+                        exit(1)
+                    }
+                }
+                print!("{}", diff.unified);
+            })
+        }
+        Options::Batch { path, concurrency } => {
+            let cases = if path.is_dir() {
+                batch::read_dir(&path)
+            } else {
+                File::open(&path)
+                    .map_err(recess::Error::from)
+                    .and_then(|file| batch::read_ndjson(BufReader::new(file)))
+            };
+            cases.and_then(|cases| {
+                let (names, requests): (Vec<_>, Vec<_>) = cases
+                    .into_iter()
+                    .map(|case| (case.name, case.request))
+                    .unzip();
+                let response = client
+                    .execute_all(requests, concurrency)
+                    .map(move |results| {
+                        let reports = names
+                            .into_iter()
+                            .zip(results)
+                            .map(|(name, result)| Report::new(name, result))
+                            .collect::<Vec<_>>();
+                        for report in &reports {
+                            println!(
+                                "{}",
+                                serde_json::to_string(report).unwrap()
+                            );
+                        }
+                        Summary::new(&reports)
+                    });
+                let summary = runtime.block_on(response)?;
+                eprintln!("{}", summary);
+                if summary.failed > 0 {
+                    exit(1)
+                }
+                Ok(())
+            })
+        }
+        Options::CheckMd { files, concurrency } => files
+            .iter()
+            .map(|file| {
+                read(file).map(|source| {
+                    markdown::extract(&source)
+                        .into_iter()
+                        .map(|snippet| {
+                            (
+                                format!("{}:{}", file.display(), snippet.line),
+                                snippet,
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|snippets| {
+                let checks = stream::iter_ok(
+                    snippets.into_iter().flat_map(|snippets| snippets),
+                )
+                .map(move |(name, snippet)| {
+                    snippet::check(&client, &snippet)
+                        .map(move |status| (name, status))
+                })
+                .buffered(concurrency.max(1))
+                .collect();
+                if !render_checks(&runtime.block_on(checks)?) {
+                    exit(1)
+                }
+                Ok(())
+            }),
+        Options::Doctest {
+            path,
+            channels,
+            concurrency,
+        } => {
+            let channels = if channels.is_empty() {
+                vec![Channel::Stable, Channel::Beta, Channel::Nightly]
+            } else {
+                channels
+            };
+            let krate = if path.join("Cargo.toml").is_file() {
+                Crate::open(&path)
+            } else {
+                Ok(Crate::default())
+            };
+            let sources = if path.is_file() {
+                Ok(vec![path.clone()])
+            } else if path.join("src").is_dir() {
+                doctest::sources(path.join("src"))
+            } else {
+                doctest::sources(&path)
+            };
+            krate.and_then(|krate| {
+                let mut examples = Vec::new();
+                for source in sources? {
+                    let name = source
+                        .strip_prefix(&path)
+                        .unwrap_or(&source)
+                        .display()
+                        .to_string();
+                    for snippet in doctest::extract(&read(&source)?) {
+                        for channel in &channels {
+                            examples.push((
+                                format!(
+                                    "{} - line {} ({})",
+                                    name, snippet.line, channel
+                                ),
+                                snippet.clone(),
+                                channel.clone(),
+                            ));
+                        }
+                    }
+                }
+                let checks = stream::iter_ok(examples)
+                    .map(move |(name, snippet, channel)| {
+                        doctest::run(&client, &krate, &snippet, channel)
+                            .map(move |status| (name, status))
+                    })
+                    .buffered(concurrency.max(1))
+                    .collect();
+                if !render_checks(&runtime.block_on(checks)?) {
+                    exit(1)
+                }
+                Ok(())
+            })
         }
     };
 
     if let Err(err) = result {
-        eprintln!("{}", err)
+        eprintln!("{}", err);
+        exit(1)
     }
 }
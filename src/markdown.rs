@@ -0,0 +1,156 @@
+//! Markdown code block extraction
+
+use snippet::{Attributes, Snippet};
+
+/// Extracts the rust code blocks fenced with backticks or tildes from
+/// Markdown source
+///
+/// Only blocks with an info string identifying them as rust, such as
+/// ```` ```rust ```` or ```` ```no_run ````, are extracted.
+pub fn extract(source: &str) -> Vec<Snippet> {
+    fenced(source, false)
+}
+
+/// Extracts fenced rust code blocks, treating blocks without an info string
+/// as rust if `untagged_rust` is true, as rustdoc does for doc comments
+pub(crate) fn fenced(source: &str, untagged_rust: bool) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    let mut open: Option<(Fence, usize, String, Vec<&str>)> = None;
+    for (idx, line) in source.lines().enumerate() {
+        let closed = match open {
+            Some((ref fence, _, _, ref mut lines)) => {
+                if fence.closed_by(line) {
+                    true
+                } else {
+                    lines.push(fence.dedent(line));
+                    false
+                }
+            }
+            _ => {
+                open = Fence::parse(line).map(|(fence, info)| {
+                    (fence, idx + 1, info.to_owned(), Vec::new())
+                });
+                false
+            }
+        };
+        if closed {
+            if let Some((_, line, info, lines)) = open.take() {
+                let attributes = Attributes::parse(&info);
+                if (untagged_rust || !info.trim().is_empty()) && attributes.rust
+                {
+                    snippets.push(Snippet {
+                        line,
+                        code: lines.join("\n"),
+                        attributes,
+                    });
+                }
+            }
+        }
+    }
+    snippets
+}
+
+/// An opening code fence
+struct Fence {
+    marker: char,
+    len: usize,
+    indent: usize,
+}
+
+impl Fence {
+    /// Parses an opening fence, returning it along with its info string
+    fn parse(line: &str) -> Option<(Fence, &str)> {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > 3 {
+            return None;
+        }
+        let rest = &line[indent..];
+        let marker = match rest.chars().next() {
+            Some(c @ '`') | Some(c @ '~') => c,
+            _ => return None,
+        };
+        let len = rest.len() - rest.trim_start_matches(marker).len();
+        if len < 3 {
+            return None;
+        }
+        let info = rest[len..].trim();
+        // backtick fences may not contain backticks in their info string
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+        Some((
+            Fence {
+                marker,
+                len,
+                indent,
+            },
+            info,
+        ))
+    }
+
+    fn closed_by(&self, line: &str) -> bool {
+        let trimmed = line.trim_start_matches(' ');
+        if line.len() - trimmed.len() > 3 {
+            return false;
+        }
+        let len = trimmed.len() - trimmed.trim_start_matches(self.marker).len();
+        len >= self.len && trimmed[len..].trim().is_empty()
+    }
+
+    /// Strips up to the fence's indentation from a content line
+    fn dedent<'a>(&self, line: &'a str) -> &'a str {
+        let spaces = line.len() - line.trim_start_matches(' ').len();
+        &line[spaces.min(self.indent)..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_rust_blocks() {
+        let source = "# title
+
+```rust
+let x = 1;
+```
+
+```sh
+cargo build
+```
+
+~~~no_run,edition2018
+loop {}
+~~~
+
+```
+plain text
+```
+";
+        let snippets = extract(source);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].line, 3);
+        assert_eq!(snippets[0].code, "let x = 1;");
+        assert_eq!(snippets[1].line, 11);
+        assert_eq!(snippets[1].code, "loop {}");
+        assert!(snippets[1].attributes.no_run);
+    }
+
+    #[test]
+    fn extracts_indented_and_nested_fences() {
+        let source = "  ````rust
+  ```
+  nested
+  ```
+  ````";
+        let snippets = extract(source);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].code, "```\nnested\n```");
+    }
+
+    #[test]
+    fn ignores_unclosed_blocks() {
+        assert!(extract("```rust\nfn main() {}").is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+//! Panic and backtrace interfaces
+
+/// A stack frame of a panic's backtrace
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frame {
+    /// The demangled function, as in `playground::main`
+    pub symbol: String,
+    /// The source file, if known
+    pub file: Option<String>,
+    /// The source line, if known
+    pub line: Option<u32>,
+}
+
+impl Frame {
+    /// Indicates if the frame belongs to the standard library or the runtime
+    /// rather than the program
+    pub fn is_std(&self) -> bool {
+        const PREFIXES: &[&str] = &[
+            "std::",
+            "core::",
+            "alloc::",
+            "<std::",
+            "<core::",
+            "<alloc::",
+            "backtrace::",
+            "panic_unwind::",
+            "__rust",
+            "__libc_start",
+            "_start",
+        ];
+        const RUNTIME: &[&str] = &[
+            "rust_begin_unwind",
+            "rust_panic",
+            "start_thread",
+            "clone",
+            "main",
+            "<unknown>",
+        ];
+        PREFIXES
+            .iter()
+            .any(|prefix| self.symbol.starts_with(prefix))
+            || RUNTIME.contains(&self.symbol.as_str())
+            || self.file.as_ref().map_or(false, |file| {
+                file.starts_with("/rustc/") || file.contains("/library/std/")
+            })
+    }
+}
+
+/// A panic parsed from a program's stderr
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Panic {
+    /// The name of the panicking thread, as in `main`
+    pub thread: String,
+    /// The panic message
+    pub message: String,
+    /// The source file of the panic, if known
+    pub file: Option<String>,
+    /// The source line of the panic, if known
+    pub line: Option<u32>,
+    /// The source column of the panic, if known
+    pub column: Option<u32>,
+    /// The backtrace's frames, innermost first, when backtraces are enabled
+    pub frames: Vec<Frame>,
+}
+
+impl Panic {
+    /// Parses the first panic in stderr, keeping only the program's own
+    /// backtrace frames
+    pub fn parse(stderr: &str) -> Option<Panic> {
+        Panic::parse_unfiltered(stderr).map(|mut panic| {
+            panic.frames.retain(|frame| !frame.is_std());
+            panic
+        })
+    }
+
+    /// Parses the first panic in stderr, keeping every backtrace frame
+    ///
+    /// Both the `panicked at 'message', location` format of older releases
+    /// and the `panicked at location:` format followed by the message on its
+    /// own lines are understood.
+    pub fn parse_unfiltered(stderr: &str) -> Option<Panic> {
+        let mut lines = stderr.lines().peekable();
+        while let Some(line) = lines.next() {
+            let idx = match line.find("' panicked at ") {
+                Some(idx) if line.starts_with("thread '") => idx,
+                _ => continue,
+            };
+            let thread = line[8..idx].to_owned();
+            let rest = &line[idx + 14..];
+            let (message, location) = if rest.starts_with('\'') {
+                // thread 'main' panicked at 'boom', src/main.rs:3:5
+                match rest.rfind("', ") {
+                    Some(end) => (rest[1..end].to_owned(), &rest[end + 3..]),
+                    _ => (rest.trim_matches('\'').to_owned(), ""),
+                }
+            } else {
+                // thread 'main' panicked at src/main.rs:3:5:
+                // boom
+                let mut message = Vec::new();
+                while let Some(line) = lines.peek().cloned() {
+                    if line.starts_with("note: ")
+                        || line.starts_with("stack backtrace:")
+                        || line.starts_with("error: ")
+                    {
+                        break;
+                    }
+                    message.push(line);
+                    lines.next();
+                }
+                (message.join("\n"), rest.trim_end_matches(':'))
+            };
+            let (file, line, column) = split_location(location);
+            let frames = frames(lines);
+            return Some(Panic {
+                thread,
+                message,
+                file,
+                line,
+                column,
+                frames,
+            });
+        }
+        None
+    }
+
+    /// Returns the panic's source location, as in `src/main.rs:3:5`
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?;
+        Some(match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                format!("{}:{}:{}", file, line, column)
+            }
+            (Some(line), None) => format!("{}:{}", file, line),
+            _ => file.clone(),
+        })
+    }
+}
+
+/// Splits a `file:line:column` location, any part of which may be missing
+fn split_location(
+    location: &str,
+) -> (Option<String>, Option<u32>, Option<u32>) {
+    let mut parts = location.rsplitn(3, ':').collect::<Vec<_>>();
+    parts.reverse();
+    match parts.as_slice() {
+        [file, line, column] => match (line.parse(), column.parse()) {
+            (Ok(line), Ok(column)) => {
+                (Some(file.to_string()), Some(line), Some(column))
+            }
+            _ => (Some(location.to_owned()), None, None),
+        },
+        [file, line] => match line.parse() {
+            Ok(line) => (Some(file.to_string()), Some(line), None),
+            _ => (Some(location.to_owned()), None, None),
+        },
+        _ if location.is_empty() => (None, None, None),
+        _ => (Some(location.to_owned()), None, None),
+    }
+}
+
+/// Parses the frames of a `stack backtrace:`, if the lines hold one
+fn frames<'a, I>(lines: I) -> Vec<Frame>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut started = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if !started {
+            started = trimmed == "stack backtrace:";
+            continue;
+        }
+        if trimmed.starts_with("at ") {
+            if let Some(frame) = frames.last_mut() {
+                let (file, line, _) = split_location(&trimmed[3..]);
+                frame.file = file;
+                frame.line = line;
+            }
+            continue;
+        }
+        let symbol = match trimmed.find(": ") {
+            Some(idx) if trimmed[..idx].chars().all(|c| c.is_digit(10)) => {
+                &trimmed[idx + 2..]
+            }
+            _ => break,
+        };
+        // `RUST_BACKTRACE=full` prefixes symbols with their address
+        let symbol = match symbol.find(" - ") {
+            Some(idx) if symbol.starts_with("0x") => &symbol[idx + 3..],
+            _ => symbol,
+        };
+        frames.push(Frame {
+            symbol: strip_hash(symbol).to_owned(),
+            file: None,
+            line: None,
+        });
+    }
+    frames
+}
+
+/// Removes the `::h` prefixed hash of a demangled symbol
+fn strip_hash(symbol: &str) -> &str {
+    match symbol.rfind("::h") {
+        Some(idx)
+            if symbol.len() - idx == 19
+                && symbol[idx + 3..].chars().all(|c| c.is_digit(16)) =>
+        {
+            &symbol[..idx]
+        }
+        _ => symbol,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKTRACE: &str = "thread 'main' panicked at src/main.rs:6:5:
+index out of bounds
+stack backtrace:
+   0: rust_begin_unwind
+             at /rustc/90b35a623/library/std/src/panicking.rs:645:5
+   1: core::panicking::panic_fmt
+             at /rustc/90b35a623/library/core/src/panicking.rs:72:14
+   2: playground::lookup::h0123456789abcdef
+             at ./src/main.rs:6:5
+   3: playground::main
+             at ./src/main.rs:2:5
+   4: core::ops::function::FnOnce::call_once
+             at /rustc/90b35a623/library/core/src/ops/function.rs:250:5
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a \
+verbose backtrace.
+";
+
+    #[test]
+    fn parses_panics_with_backtraces() {
+        let panic = Panic::parse(BACKTRACE).unwrap();
+        assert_eq!(panic.thread, "main");
+        assert_eq!(panic.message, "index out of bounds");
+        assert_eq!(panic.location(), Some("src/main.rs:6:5".into()));
+        assert_eq!(
+            panic.frames,
+            vec![
+                Frame {
+                    symbol: "playground::lookup".into(),
+                    file: Some("./src/main.rs".into()),
+                    line: Some(6),
+                },
+                Frame {
+                    symbol: "playground::main".into(),
+                    file: Some("./src/main.rs".into()),
+                    line: Some(2),
+                },
+            ]
+        );
+        assert_eq!(Panic::parse_unfiltered(BACKTRACE).unwrap().frames.len(), 5);
+    }
+
+    #[test]
+    fn parses_legacy_panics() {
+        let panic = Panic::parse(
+            "thread 'worker' panicked at 'boom', src/lib.rs:3:9\n",
+        )
+        .unwrap();
+        assert_eq!(panic.thread, "worker");
+        assert_eq!(panic.message, "boom");
+        assert_eq!(
+            (panic.file, panic.line, panic.column),
+            (Some("src/lib.rs".into()), Some(3), Some(9))
+        );
+        assert!(panic.frames.is_empty());
+    }
+}
@@ -0,0 +1,347 @@
+//! Multi-file project interfaces
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use {Error, ExecuteRequest};
+
+/// A crate made of several files and, optionally, dependencies
+///
+/// Projects are sent as is to backends accepting multiple files, see
+/// `Client::execute_project`. The playground only accepts a single file,
+/// see `bundle`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    /// File contents by path relative to the project root, such as
+    /// `src/main.rs`
+    pub files: BTreeMap<PathBuf, String>,
+    /// Dependency version requirements by crate name
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+}
+
+/// A project sent to backends accepting multiple files
+///
+/// The project is also bundled into the request's `code`, for backends
+/// which only read it.
+#[derive(Debug, Serialize)]
+pub(crate) struct Request {
+    #[serde(flatten)]
+    request: ExecuteRequest,
+    #[serde(flatten)]
+    project: Project,
+}
+
+impl Request {
+    pub(crate) fn new(request: ExecuteRequest, project: Project) -> Self {
+        Request { request, project }
+    }
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Dependency {
+    Version(String),
+    Detailed {
+        #[serde(default)]
+        version: Option<String>,
+    },
+}
+
+impl Project {
+    /// Returns a new, empty `Project`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a file at a path relative to the project root
+    pub fn file<P, C>(&mut self, path: P, contents: C) -> &mut Self
+    where
+        P: Into<PathBuf>,
+        C: Into<String>,
+    {
+        self.files.insert(path.into(), contents.into());
+        self
+    }
+
+    /// Adds a dependency on a crate
+    pub fn dependency<N, V>(&mut self, name: N, version: V) -> &mut Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.dependencies.insert(name.into(), version.into());
+        self
+    }
+
+    /// Reads the `.rs` files of a directory and the dependencies of its
+    /// `Cargo.toml`, if it has one
+    ///
+    /// Only the `src` directory is read when there is one.
+    pub fn open<P>(dir: P) -> Result<Project, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let mut project = Project::new();
+        let src = dir.join("src");
+        let sources = if src.is_dir() { src } else { dir.to_path_buf() };
+        for path in rust_files(&sources)? {
+            let relative = path.strip_prefix(dir).unwrap_or(&path).to_owned();
+            project.files.insert(relative, read(&path)?);
+        }
+        let manifest = dir.join("Cargo.toml");
+        if manifest.is_file() {
+            let manifest = toml::from_str::<Manifest>(&read(&manifest)?)?;
+            for (name, dependency) in manifest.dependencies {
+                let version = match dependency {
+                    Dependency::Version(version) => version,
+                    Dependency::Detailed { version } => {
+                        version.unwrap_or_else(|| "*".into())
+                    }
+                };
+                project.dependencies.insert(name, version);
+            }
+        }
+        Ok(project)
+    }
+
+    /// Returns the path of the crate root, the first of `src/main.rs`,
+    /// `src/lib.rs`, `main.rs` and `lib.rs` in the project
+    pub fn root(&self) -> Option<&Path> {
+        ["src/main.rs", "src/lib.rs", "main.rs", "lib.rs"]
+            .iter()
+            .map(Path::new)
+            .find(|path| self.files.contains_key(*path))
+    }
+
+    /// Bundles the project into a single file by inlining the files of
+    /// out-of-line `mod` declarations, as required by the playground
+    ///
+    /// Dependencies can't be part of the bundle, so projects declaring any
+    /// are an error rather than silently losing them.
+    pub fn bundle(&self) -> Result<String, Error> {
+        if !self.dependencies.is_empty() {
+            return Err(Error::Project(format!(
+                "dependencies can't be sent to the playground: {}",
+                self.dependencies
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        self.bundle_files()
+    }
+
+    /// Bundles the project's files into a single file, leaving out its
+    /// dependencies
+    pub(crate) fn bundle_files(&self) -> Result<String, Error> {
+        let root = self.root().ok_or_else(|| {
+            Error::Project("no main.rs or lib.rs crate root".into())
+        })?;
+        let dir = root.parent().unwrap_or_else(|| Path::new(""));
+        self.inline(root, dir)
+    }
+
+    /// Inlines the modules declared by a file, whose own modules are found
+    /// in `dir`
+    fn inline(&self, path: &Path, dir: &Path) -> Result<String, Error> {
+        let source = &self.files[path];
+        let mut out = String::with_capacity(source.len());
+        // the attributes on lines of their own before the next item
+        let mut attributes = String::new();
+        for line in source.lines() {
+            let name = match declared_module(line) {
+                Some(name) => name,
+                _ => {
+                    let trimmed = line.trim();
+                    if trimmed.starts_with("#[") {
+                        attributes.push_str(trimmed);
+                    } else if !trimmed.is_empty() && !trimmed.starts_with("//")
+                    {
+                        attributes.clear();
+                    }
+                    out.push_str(line);
+                    out.push('\n');
+                    continue;
+                }
+            };
+            attributes.push_str(line);
+            if has_path_attribute(&attributes) {
+                return Err(Error::Project(format!(
+                    "#[path] isn't supported for module {} declared in {}",
+                    name,
+                    path.display()
+                )));
+            }
+            attributes.clear();
+            let module_dir = dir.join(name);
+            let file = Some(dir.join(format!("{}.rs", name)))
+                .filter(|file| self.files.contains_key(file))
+                .unwrap_or_else(|| module_dir.join("mod.rs"));
+            if !self.files.contains_key(&file) {
+                return Err(Error::Project(format!(
+                    "file not found for module {} declared in {}",
+                    name,
+                    path.display()
+                )));
+            }
+            let declaration = line.trim_end().trim_end_matches(';');
+            out.push_str(&format!("{} {{\n", declaration));
+            out.push_str(&self.inline(&file, &module_dir)?);
+            out.push_str("}\n");
+        }
+        Ok(out)
+    }
+}
+
+/// Returns the name of the module of a `mod name;` declaration
+fn declared_module(line: &str) -> Option<&str> {
+    let mut rest = line.trim();
+    if !rest.ends_with(';') {
+        return None;
+    }
+    // skip attributes, such as `#[cfg(test)]`
+    while rest.starts_with("#[") {
+        rest = rest[rest.find(']')? + 1..].trim_start();
+    }
+    if rest.starts_with("pub(") {
+        rest = &rest[rest.find(')')? + 1..];
+    } else if rest.starts_with("pub ") {
+        rest = &rest[3..];
+    }
+    let rest = rest.trim_start();
+    if !rest.starts_with("mod ") {
+        return None;
+    }
+    let name = rest[4..rest.len() - 1].trim();
+    if !name.is_empty()
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Indicates if attributes include a `#[path = "..."]` attribute
+fn has_path_attribute(attributes: &str) -> bool {
+    attributes
+        .split("#[")
+        .skip(1)
+        .map(str::trim_start)
+        .filter(|attribute| attribute.starts_with("path"))
+        .any(|attribute| attribute[4..].trim_start().starts_with('='))
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Lists the `.rs` files under a directory
+fn rust_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(rust_files(&path)?);
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_modules_inline() {
+        let mut project = Project::new();
+        project
+            .file("src/main.rs", "mod a;\npub(crate) mod b;\nfn main() {}")
+            .file("src/a.rs", "mod c;\npub fn a() {}")
+            .file("src/a/c.rs", "pub fn c() {}")
+            .file("src/b/mod.rs", "#![allow(unused)]\npub fn b() {}");
+        assert_eq!(
+            project.bundle().unwrap(),
+            "mod a {\nmod c {\npub fn c() {}\n}\npub fn a() {}\n}\n\
+             pub(crate) mod b {\n#![allow(unused)]\npub fn b() {}\n}\n\
+             fn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn bundle_reports_missing_modules() {
+        let mut project = Project::new();
+        project.file("main.rs", "mod gone;");
+        assert!(project.bundle().is_err());
+        assert!(Project::new().bundle().is_err());
+    }
+
+    #[test]
+    fn bundle_rejects_path_attributes() {
+        let mut project = Project::new();
+        project
+            .file("src/main.rs", "#[path = \"b.rs\"]\nmod a;\nfn main() {}")
+            .file("src/a.rs", "")
+            .file("src/b.rs", "");
+        assert!(project.bundle().is_err());
+        project.file("src/main.rs", "#[path=\"b.rs\"] mod a;\nfn main() {}");
+        assert!(project.bundle().is_err());
+        project.file("src/main.rs", "#[cfg(test)]\nmod a;\nfn main() {}");
+        assert!(project.bundle().is_ok());
+    }
+
+    #[test]
+    fn bundle_rejects_dependencies() {
+        let mut project = Project::new();
+        project
+            .file("src/main.rs", "fn main() {}")
+            .dependency("rand", "0.8");
+        assert!(project.bundle().is_err());
+        assert_eq!(project.bundle_files().unwrap(), "fn main() {}\n");
+    }
+
+    #[test]
+    fn requests_carry_files_and_dependencies() {
+        let mut project = Project::new();
+        project
+            .file("src/main.rs", "fn main() {}")
+            .dependency("rand", "0.8");
+        let request = Request::new(
+            ExecuteRequest::builder("fn main() {}").build().unwrap(),
+            project,
+        );
+        let json = ::serde_json::to_value(&request).unwrap();
+        assert_eq!(json["code"], "fn main() {}");
+        assert_eq!(json["files"]["src/main.rs"], "fn main() {}");
+        assert_eq!(json["dependencies"]["rand"], "0.8");
+    }
+
+    #[test]
+    fn recognizes_module_declarations() {
+        assert_eq!(declared_module("  pub mod foo;"), Some("foo"));
+        assert_eq!(declared_module("#[cfg(test)] mod tests;"), Some("tests"));
+        assert_eq!(
+            declared_module("#[cfg(unix)] #[allow(unused)] pub mod x;"),
+            Some("x")
+        );
+        assert_eq!(declared_module("mod foo { }"), None);
+        assert_eq!(declared_module("// mod foo;"), None);
+    }
+}
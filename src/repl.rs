@@ -0,0 +1,379 @@
+//! Read-eval-print loop interfaces
+//!
+//! A `Session` accumulates `use` items, item definitions and statements.
+//! Each entry re-executes the whole program, with a marker printed just
+//! before the newest entry so that only its output is shown.
+
+use std::str::FromStr;
+
+use compile::Target;
+use execute;
+use {Channel, CompileRequest, Edition, ExecuteRequest, Mode};
+
+/// Printed to stdout and stderr before the newest entry runs
+pub const MARKER: &str = "--recess-repl-marker--";
+
+/// The kind of a REPL entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    /// A `use` declaration or `extern crate`
+    Use,
+    /// An item definition, such as a function or a struct
+    Item,
+    /// A statement, run for its effects
+    Statement,
+    /// An expression, whose value is printed with `{:?}`
+    Expression,
+}
+
+impl Kind {
+    /// Classifies an entry
+    pub fn of(entry: &str) -> Kind {
+        let entry = entry.trim();
+        let mut rest = entry;
+        // skip attributes, such as `#[derive(Debug)]`
+        while rest.starts_with("#[") {
+            rest = match rest.find(']') {
+                Some(idx) => rest[idx + 1..].trim_start(),
+                _ => break,
+            };
+        }
+        if rest.starts_with("pub(") {
+            rest = rest.find(')').map_or(rest, |idx| &rest[idx + 1..]);
+        }
+        let words = rest
+            .split_whitespace()
+            .filter(|word| *word != "pub" && *word != "unsafe")
+            .collect::<Vec<_>>();
+        match words.first().cloned().unwrap_or_default() {
+            "use" => Kind::Use,
+            "extern" if words.get(1) == Some(&"crate") => Kind::Use,
+            "fn" | "struct" | "enum" | "union" | "impl" | "trait" | "mod"
+            | "const" | "static" | "type" | "extern" | "async"
+            | "macro_rules!" => Kind::Item,
+            // loops evaluate to `()`, unlike `if`, `match` or blocks
+            "let" | "for" | "while" => Kind::Statement,
+            _ if entry.ends_with(';') => Kind::Statement,
+            _ => Kind::Expression,
+        }
+    }
+}
+
+/// Indicates if an entry's brackets are balanced, as opposed to an entry
+/// continued on following lines
+///
+/// Brackets within strings, char literals and comments aren't counted.
+pub fn is_complete(entry: &str) -> bool {
+    let chars = entry.chars().collect::<Vec<_>>();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let next = chars.get(idx + 1).cloned();
+        match chars[idx] {
+            '\\' if in_string => idx += 1,
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '/' if next == Some('/') => {
+                while idx < chars.len() && chars[idx] != '\n' {
+                    idx += 1;
+                }
+            }
+            '/' if next == Some('*') => {
+                idx += 2;
+                while idx < chars.len()
+                    && !(chars[idx] == '*' && chars.get(idx + 1) == Some(&'/'))
+                {
+                    idx += 1;
+                }
+                idx += 1;
+            }
+            // char literals, such as `'{'` or `'\''`, unlike lifetimes
+            '\'' if next == Some('\\') => {
+                idx += 2;
+                while idx + 1 < chars.len() && chars[idx + 1] != '\'' {
+                    idx += 1;
+                }
+                idx += 1;
+            }
+            '\'' if chars.get(idx + 2) == Some(&'\'') => idx += 2,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => (),
+        }
+        idx += 1;
+    }
+    depth <= 0 && !in_string
+}
+
+/// A REPL meta command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `:channel [name]` shows or sets the release channel
+    Channel(Option<Channel>),
+    /// `:mode [name]` shows or sets the compilation mode
+    Mode(Option<Mode>),
+    /// `:edition [year]` shows or sets the edition
+    Edition(Option<Edition>),
+    /// `:reset` forgets every entry
+    Reset,
+    /// `:show` prints the accumulated program
+    Show,
+    /// `:asm` prints the assembly of the accumulated program
+    Asm,
+    /// `:help` lists the commands
+    Help,
+    /// `:quit` ends the session
+    Quit,
+}
+
+impl Command {
+    /// Describes the commands
+    pub fn help() -> &'static str {
+        ":channel [stable|beta|nightly]  show or set the release channel
+:mode [debug|release]           show or set the compilation mode
+:edition [2015|2018|2021]       show or set the edition
+:reset                          forget every entry
+:show                           print the accumulated program
+:asm                            print the program's assembly
+:help                           print this help
+:quit                           end the session"
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        let mut words = s.trim().trim_start_matches(':').split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let arg = words.next();
+        fn parse<T>(arg: Option<&str>) -> Result<Option<T>, String>
+        where
+            T: FromStr<Err = &'static str>,
+        {
+            arg.map(str::parse).transpose().map_err(String::from)
+        }
+        match name {
+            "channel" => parse(arg).map(Command::Channel),
+            "mode" => parse(arg).map(Command::Mode),
+            "edition" => parse(arg).map(Command::Edition),
+            "reset" => Ok(Command::Reset),
+            "show" => Ok(Command::Show),
+            "asm" => Ok(Command::Asm),
+            "help" => Ok(Command::Help),
+            "quit" | "q" => Ok(Command::Quit),
+            _ => Err(format!("unknown command :{}, see :help", name)),
+        }
+    }
+}
+
+/// The entries and options of a REPL session
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Session {
+    /// `use` declarations, in order
+    uses: Vec<String>,
+    /// Item definitions, in order
+    items: Vec<String>,
+    /// Statements of `fn main`, in order
+    statements: Vec<String>,
+    /// rust release channel, defaults to the client's channel
+    pub channel: Option<Channel>,
+    /// compilation mode, defaults to the client's mode
+    pub mode: Option<Mode>,
+    /// rust edition, defaults to the client's edition
+    pub edition: Option<Edition>,
+}
+
+impl Session {
+    /// Returns a new, empty `Session`
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the accumulated program followed, if any, by the output
+    /// marker and a new entry
+    pub fn program(&self, entry: Option<&str>) -> String {
+        let mut uses = self.uses.clone();
+        let mut items = self.items.clone();
+        let mut newest = None;
+        if let Some(entry) = entry {
+            match Kind::of(entry) {
+                Kind::Use => uses.push(entry.trim().to_owned()),
+                Kind::Item => items.push(entry.trim().to_owned()),
+                Kind::Statement => newest = Some(entry.trim().to_owned()),
+                Kind::Expression => {
+                    newest =
+                        Some(format!("println!(\"{{:?}}\", {});", entry.trim()))
+                }
+            }
+        }
+        let mut code = String::new();
+        for declaration in &uses {
+            code.push_str(declaration);
+            code.push('\n');
+        }
+        for item in &items {
+            code.push_str(item);
+            code.push_str("\n\n");
+        }
+        code.push_str("fn main() {\n");
+        for statement in &self.statements {
+            code.push_str(&indent(statement));
+        }
+        if entry.is_some() {
+            code.push_str(&format!(
+                "    println!(\"{0}\");\n    eprintln!(\"{0}\");\n",
+                MARKER
+            ));
+        }
+        if let Some(newest) = newest {
+            code.push_str(&indent(&newest));
+        }
+        code.push_str("}\n");
+        code
+    }
+
+    /// Returns the request executing a new entry
+    pub fn request(&self, entry: &str) -> ExecuteRequest {
+        ExecuteRequest::builder(self.program(Some(entry)))
+            .channel(self.channel.clone())
+            .mode(self.mode.clone())
+            .edition(self.edition.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// Returns the request compiling the accumulated program to assembly
+    pub fn asm_request(&self) -> CompileRequest {
+        CompileRequest::builder(self.program(None))
+            .target(Target::Asm)
+            .channel(self.channel.clone())
+            .mode(self.mode.clone())
+            .edition(self.edition.clone())
+            .build()
+            .unwrap()
+    }
+
+    /// Keeps an entry which ran successfully
+    ///
+    /// Expressions are kept as statements borrowing their value, so that
+    /// they aren't printed again and don't move out of variables.
+    pub fn push(&mut self, entry: &str) {
+        let entry = entry.trim().to_owned();
+        match Kind::of(&entry) {
+            Kind::Use => self.uses.push(entry),
+            Kind::Item => self.items.push(entry),
+            Kind::Statement => self.statements.push(entry),
+            Kind::Expression => {
+                self.statements.push(format!("let _ = &({});", entry))
+            }
+        }
+    }
+
+    /// Forgets every entry, keeping the options
+    pub fn reset(&mut self) {
+        self.uses.clear();
+        self.items.clear();
+        self.statements.clear();
+    }
+}
+
+/// Returns the stdout and stderr written by the newest entry of an execution
+///
+/// On build failures, which never reach the marker, stderr is the build's.
+pub fn newest_output(response: &execute::Response) -> (&str, &str) {
+    let stdout = after_marker(&response.stdout).unwrap_or_default();
+    let stderr = after_marker(response.program_stderr())
+        .unwrap_or_else(|| response.build_stderr());
+    (stdout, stderr)
+}
+
+/// Returns the output following the marker line
+fn after_marker(output: &str) -> Option<&str> {
+    let idx = output.find(MARKER)?;
+    let rest = &output[idx + MARKER.len()..];
+    Some(rest.trim_start_matches('\n'))
+}
+
+/// Indents each line of a statement into the body of `fn main`
+fn indent(statement: &str) -> String {
+    statement
+        .lines()
+        .map(|line| format!("    {}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_entries() {
+        assert_eq!(Kind::of("use std::collections::HashMap;"), Kind::Use);
+        assert_eq!(Kind::of("#[derive(Debug)]\nstruct Foo;"), Kind::Item);
+        assert_eq!(Kind::of("pub(crate) fn foo() {}"), Kind::Item);
+        assert_eq!(Kind::of("let x = 1;"), Kind::Statement);
+        assert_eq!(Kind::of("for i in 0..3 { f(i) }"), Kind::Statement);
+        assert_eq!(Kind::of("x + 1"), Kind::Expression);
+        assert_eq!(Kind::of("if x { 1 } else { 2 }"), Kind::Expression);
+        assert_eq!(Kind::of("match v {\n    _ => 1,\n}"), Kind::Expression);
+        assert_eq!(Kind::of("while x < 3 { x += 1 }"), Kind::Statement);
+    }
+
+    #[test]
+    fn detects_incomplete_entries() {
+        assert!(!is_complete("fn foo() {"));
+        assert!(is_complete("fn foo() {\n}"));
+        assert!(is_complete("let s = \"{\";"));
+        assert!(is_complete("let c = '{';"));
+        assert!(is_complete("let c = '\\'';"));
+        assert!(is_complete("fn f<'a>(x: &'a str) -> &'a str { x }"));
+        assert!(is_complete("let x = 1; // {"));
+        assert!(is_complete("let x = /* { */ 1;"));
+        assert!(!is_complete("fn f() { // }"));
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            ":channel nightly".parse::<Command>(),
+            Ok(Command::Channel(Some(Channel::Nightly)))
+        );
+        assert_eq!(":mode".parse::<Command>(), Ok(Command::Mode(None)));
+        assert!(":edition 2000".parse::<Command>().is_err());
+        assert!(":nope".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn accumulates_programs() {
+        let mut session = Session::new();
+        session.push("use std::fmt;");
+        session.push("fn double(x: i32) -> i32 { x * 2 }");
+        session.push("let x = double(2);");
+        session.push("x");
+        assert_eq!(
+            session.program(Some("x + 1")),
+            "use std::fmt;\nfn double(x: i32) -> i32 { x * 2 }\n\n\
+             fn main() {\n    let x = double(2);\n    let _ = &(x);\n    \
+             println!(\"--recess-repl-marker--\");\n    \
+             eprintln!(\"--recess-repl-marker--\");\n    \
+             println!(\"{:?}\", x + 1);\n}\n"
+        );
+        assert!(!session.program(None).contains(MARKER));
+        session.reset();
+        assert!(!session.program(None).contains("double"));
+    }
+
+    #[test]
+    fn shows_only_newest_output() {
+        let response = execute::Response {
+            success: true,
+            stdout: format!("old\n{}\nnew\n", MARKER),
+            stderr: format!(
+                "     Running `target/debug/playground`\n{}\n",
+                MARKER
+            ),
+        };
+        assert_eq!(newest_output(&response), ("new\n", ""));
+    }
+}
@@ -0,0 +1,365 @@
+//! Rustdoc-style code snippet interfaces
+
+use futures::future;
+use futures::Future as StdFuture;
+use hyper::client::connect::Connect;
+
+use execute::{self, Outcome};
+use {
+    Channel, Client, CompileRequest, CrateType, Edition, Error, ExecuteRequest,
+    Future,
+};
+
+/// Rustdoc code block attributes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Attributes {
+    /// Indicates if the block holds rust code
+    pub rust: bool,
+    /// Skip the block entirely
+    pub ignore: bool,
+    /// Compile the block but don't run it
+    pub no_run: bool,
+    /// Running the block is expected to panic
+    pub should_panic: bool,
+    /// Compiling the block is expected to fail
+    pub compile_fail: bool,
+    /// Compile the block as a test harness rather than wrapping it in main
+    pub test_harness: bool,
+    /// The edition to compile the block with
+    pub edition: Option<Edition>,
+}
+
+impl Attributes {
+    /// Parses a code fence info string such as `rust,no_run`
+    ///
+    /// As with rustdoc, blocks without an info string are considered rust,
+    /// as are blocks whose info string only holds known attributes
+    pub fn parse(info: &str) -> Self {
+        let mut attrs = Attributes {
+            rust: true,
+            ..Default::default()
+        };
+        let mut seen_rust = false;
+        for token in info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+        {
+            match token {
+                "rust" => seen_rust = true,
+                "ignore" => attrs.ignore = true,
+                "no_run" => attrs.no_run = true,
+                "should_panic" => attrs.should_panic = true,
+                "compile_fail" => attrs.compile_fail = true,
+                "test_harness" => attrs.test_harness = true,
+                "edition2015" => attrs.edition = Some(Edition::Edition2015),
+                "edition2018" => attrs.edition = Some(Edition::Edition2018),
+                "edition2021" => attrs.edition = Some(Edition::Edition2021),
+                // rustdoc attributes that don't change how a block is checked
+                "allow_fail" => (),
+                _ => attrs.rust = false,
+            }
+        }
+        attrs.rust = attrs.rust || seen_rust;
+        attrs
+    }
+
+    /// Returns how a block with these attributes should be checked
+    pub fn expectation(&self) -> Expectation {
+        if self.ignore {
+            Expectation::Ignore
+        } else if self.compile_fail {
+            Expectation::CompileFail
+        } else if self.no_run {
+            Expectation::Compile
+        } else if self.should_panic {
+            Expectation::Panic
+        } else {
+            Expectation::Run
+        }
+    }
+}
+
+/// How a snippet is checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expectation {
+    /// Not checked
+    Ignore,
+    /// Compiles successfully
+    Compile,
+    /// Fails to compile
+    CompileFail,
+    /// Runs successfully
+    Run,
+    /// Compiles, then fails when run
+    Panic,
+}
+
+/// A code block extracted from documentation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    /// The 1-based line of the block's opening fence
+    pub line: usize,
+    /// The block's contents
+    pub code: String,
+    /// The block's attributes
+    pub attributes: Attributes,
+}
+
+impl Snippet {
+    /// Returns the snippet's code as a complete program, including any
+    /// hidden lines
+    pub fn program(&self) -> String {
+        let code = unhide(&self.code);
+        if self.attributes.test_harness {
+            code
+        } else {
+            wrap(&code)
+        }
+    }
+}
+
+/// Reveals lines hidden from rendered docs with a leading `# `
+///
+/// As with rustdoc, a lone `#` is an empty line and `##` escapes a literal `#`
+pub fn unhide(code: &str) -> String {
+    code.lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed == "#" {
+                ""
+            } else if trimmed.starts_with("##") {
+                &trimmed[1..]
+            } else if trimmed.starts_with("# ") {
+                &trimmed[2..]
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wraps code in a `fn main` as rustdoc does, unless it already declares one
+///
+/// Crate attributes and `extern crate` declarations are kept at the top level.
+pub fn wrap(code: &str) -> String {
+    let mut prelude = String::from("#![allow(unused)]\n");
+    let mut body = String::new();
+    let declares_main = code.contains("fn main");
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#![")
+            || (!declares_main && trimmed.starts_with("extern crate"))
+        {
+            prelude.push_str(line);
+            prelude.push('\n');
+        } else {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    if declares_main {
+        format!("{}{}", prelude, body)
+    } else {
+        format!("{}fn main() {{\n{}}}\n", prelude, body)
+    }
+}
+
+/// The result of checking a snippet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// The snippet behaved as expected
+    Ok,
+    /// The snippet was not checked
+    Ignored,
+    /// The snippet did not behave as expected
+    Failed {
+        /// Describes the unexpected behavior
+        reason: String,
+        /// Stdout line output
+        stdout: String,
+        /// Stderr line output
+        stderr: String,
+    },
+}
+
+/// Checks that a snippet behaves as its attributes expect
+pub fn check<C>(client: &Client<C>, snippet: &Snippet) -> Future<Status>
+where
+    C: Clone + Connect + 'static,
+{
+    check_program(client, snippet.program(), &snippet.attributes, None)
+}
+
+/// Checks that a program behaves as a block with the given attributes is
+/// expected to
+///
+/// Test harness blocks are executed with `tests` enabled as a library.
+pub fn check_program<C>(
+    client: &Client<C>,
+    program: String,
+    attributes: &Attributes,
+    channel: Option<Channel>,
+) -> Future<Status>
+where
+    C: Clone + Connect + 'static,
+{
+    let expectation = attributes.expectation();
+    let response: Future<execute::Response> = match expectation {
+        Expectation::Ignore => return Box::new(future::ok(Status::Ignored)),
+        Expectation::Compile | Expectation::CompileFail => {
+            let mut request = CompileRequest::builder(program);
+            request.edition(attributes.edition.clone());
+            request.channel(channel);
+            if attributes.test_harness {
+                request.tests(true).crate_type(CrateType::Library);
+            }
+            Box::new(client.compile(request.build().unwrap()).map(|response| {
+                execute::Response {
+                    success: response.success,
+                    stdout: response.stdout,
+                    stderr: response.stderr,
+                }
+            }))
+        }
+        Expectation::Run | Expectation::Panic => {
+            let mut request = ExecuteRequest::builder(program);
+            request.edition(attributes.edition.clone());
+            request.channel(channel);
+            if attributes.test_harness {
+                request.tests(true).crate_type(CrateType::Library);
+            }
+            Box::new(client.execute(request.build().unwrap()))
+        }
+    };
+    Box::new(
+        response
+            .map(move |response| judge(expectation, response))
+            .or_else(|err| match err {
+                // requests which can't succeed fail the snippet, not the check
+                Error::Invalid(err) => Ok(Status::Failed {
+                    reason: err.to_string(),
+                    stdout: String::new(),
+                    stderr: String::new(),
+                }),
+                err => Err(err),
+            }),
+    )
+}
+
+/// Returns the status of a snippet given the response to its request
+///
+/// Snippets expected to panic only pass when they compiled, ran and
+/// panicked.
+fn judge(expectation: Expectation, response: execute::Response) -> Status {
+    let reason = match (expectation, response.success) {
+        (Expectation::Compile, false) => "failed to compile",
+        (Expectation::CompileFail, true) => {
+            "compiled successfully but should have failed"
+        }
+        (Expectation::Run, false) => "failed to run",
+        (Expectation::Panic, true) => {
+            "ran successfully but should have panicked"
+        }
+        (Expectation::Panic, false) => match response.outcome() {
+            Outcome::Panicked { .. } => return Status::Ok,
+            Outcome::CompileError => "failed to compile",
+            _ => "failed without panicking",
+        },
+        _ => return Status::Ok,
+    };
+    Status::Failed {
+        reason: reason.into(),
+        stdout: response.stdout,
+        stderr: response.stderr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_attributes() {
+        assert_eq!(
+            Attributes::parse("rust,no_run edition2018"),
+            Attributes {
+                rust: true,
+                no_run: true,
+                edition: Some(Edition::Edition2018),
+                ..Default::default()
+            }
+        );
+        assert!(Attributes::parse("").rust);
+        assert!(Attributes::parse("should_panic").rust);
+        assert!(!Attributes::parse("sh").rust);
+        assert!(!Attributes::parse("text,ignore").rust);
+    }
+
+    #[test]
+    fn attributes_determine_expectations() {
+        assert_eq!(
+            Attributes::parse("rust,ignore").expectation(),
+            Expectation::Ignore
+        );
+        assert_eq!(
+            Attributes::parse("compile_fail").expectation(),
+            Expectation::CompileFail
+        );
+        assert_eq!(
+            Attributes::parse("no_run").expectation(),
+            Expectation::Compile
+        );
+        assert_eq!(
+            Attributes::parse("should_panic").expectation(),
+            Expectation::Panic
+        );
+        assert_eq!(Attributes::parse("rust").expectation(), Expectation::Run);
+    }
+
+    #[test]
+    fn wraps_bare_snippets_in_main() {
+        assert_eq!(
+            wrap("#![feature(test)]\nextern crate foo;\nlet x = 1;"),
+            "#![allow(unused)]\n#![feature(test)]\nextern crate foo;\n\
+             fn main() {\nlet x = 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn unhides_hidden_lines() {
+        assert_eq!(
+            unhide("# use std::fmt;\n#\n    # let x = 1;\n## not hidden\nx"),
+            "use std::fmt;\n\nlet x = 1;\n# not hidden\nx"
+        );
+    }
+
+    #[test]
+    fn should_panic_snippets_must_compile_and_panic() {
+        let response = |stderr: &str| execute::Response {
+            success: false,
+            stdout: String::new(),
+            stderr: stderr.into(),
+        };
+        let compile_error = response(
+            "   Compiling playground v0.0.1 (/playground)\n\
+             error[E0425]: cannot find value `x` in this scope\n",
+        );
+        match judge(Expectation::Panic, compile_error) {
+            Status::Failed { reason, .. } => {
+                assert_eq!(reason, "failed to compile")
+            }
+            status => panic!("unexpected status {:?}", status),
+        }
+        let panicked = response(
+            "     Running `target/debug/playground`\n\
+             thread 'main' panicked at src/main.rs:3:5:\nboom\n",
+        );
+        assert_eq!(judge(Expectation::Panic, panicked), Status::Ok);
+    }
+
+    #[test]
+    fn leaves_snippets_declaring_main() {
+        assert_eq!(wrap("fn main() {}"), "#![allow(unused)]\nfn main() {}\n");
+    }
+}
@@ -0,0 +1,110 @@
+//! Request validation interfaces
+
+use hyper::Uri;
+
+use {Channel, CrateType, Mode, OptLevel};
+
+/// The host of the public playground
+const PUBLIC_HOST: &str = "play.rust-lang.org";
+
+/// Describes why a request can't succeed, found before it is sent
+#[derive(Fail, Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// `Target::Wasm` was requested on a channel other than nightly
+    #[fail(
+        display = "the wasm target is only available on the nightly channel"
+    )]
+    WasmRequiresNightly,
+    /// An assembly flavor was set for a target other than `Target::Asm`
+    #[fail(display = "an assembly flavor only applies to the asm target")]
+    AsmFlavorWithoutAsm,
+    /// Tests were requested for a binary crate which declares none
+    #[fail(display = "tests were requested but the code declares no #[test]")]
+    NoTests,
+    /// The playground only compiles at the opt-levels of its modes, 0 and 3
+    #[fail(display = "opt-level {} is not supported, only 0 and 3 are", _0)]
+    UnsupportedOptLevel(OptLevel),
+    /// An opt-level was set along with a mode compiling at another level
+    #[fail(display = "opt-level {} conflicts with {} mode", _0, _1)]
+    OptLevelConflictsWithMode(OptLevel, Mode),
+    /// `#![feature]` attributes were used on a channel other than nightly
+    #[fail(display = "#![feature] attributes are only allowed on nightly")]
+    FeatureRequiresNightly,
+    /// rustc arguments, cfgs or environment variables were sent to the
+    /// public playground, which ignores them
+    #[fail(
+        display = "the public playground ignores rustc arguments, cfgs and \
+                   environment variables"
+    )]
+    UnsupportedRustcOptions,
+}
+
+/// Checks that binary crates run as tests declare at least one test
+pub(crate) fn tests(
+    tests: bool,
+    crate_type: &CrateType,
+    code: &str,
+) -> Result<(), ValidationError> {
+    if tests && *crate_type == CrateType::Binary && !code.contains("#[test]") {
+        Err(ValidationError::NoTests)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that an opt-level maps to the mode it is sent with
+pub(crate) fn opt_level(
+    opt_level: &Option<OptLevel>,
+    mode: &Option<Mode>,
+) -> Result<(), ValidationError> {
+    let opt_level = match *opt_level {
+        Some(ref opt_level) => opt_level,
+        _ => return Ok(()),
+    };
+    match (opt_level.mode(), mode) {
+        (None, _) => {
+            Err(ValidationError::UnsupportedOptLevel(opt_level.clone()))
+        }
+        (Some(ref implied), &Some(ref mode)) if implied != mode => {
+            Err(ValidationError::OptLevelConflictsWithMode(
+                opt_level.clone(),
+                mode.clone(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that code using unstable features is sent to the nightly channel
+pub(crate) fn features(
+    channel: &Option<Channel>,
+    code: &str,
+) -> Result<(), ValidationError> {
+    let gated = code.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("#![feature(") || line.starts_with("#![feature (")
+    });
+    if gated && *channel != Some(Channel::Nightly) {
+        Err(ValidationError::FeatureRequiresNightly)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that rustc arguments, cfgs and environment variables aren't sent
+/// to the public playground, which would silently ignore them
+pub(crate) fn rustc_options(
+    host: &Uri,
+    rustc_options: bool,
+) -> Result<(), ValidationError> {
+    if rustc_options && is_public(host) {
+        Err(ValidationError::UnsupportedRustcOptions)
+    } else {
+        Ok(())
+    }
+}
+
+/// Indicates if a host is the public playground's
+pub(crate) fn is_public(host: &Uri) -> bool {
+    host.host() == Some(PUBLIC_HOST)
+}